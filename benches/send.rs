@@ -0,0 +1,55 @@
+//! Compares the overhead of the protected `Value::send` and `Value::constant`
+//! against raw `rb_funcallv` and `rb_const_get`.
+//!
+//! Run with `cargo bench --bench send`. Each line reports nanoseconds per call and
+//! the ratio to the raw C call at the top of its group.
+
+use mri_sys::helpers::{std::classes, to_ruby, Value, Vm};
+use std::time::Instant;
+
+const ITERATIONS: u32 = 1_000_000;
+
+fn main() {
+    let vm = Vm::init().unwrap();
+    let receiver = to_ruby::integer(1);
+    let argument = to_ruby::integer(2);
+
+    let method_id = unsafe { mri_sys::rb_intern(b"+\0".as_ptr() as _) };
+    let raw = bench("rb_funcallv", 0.0, || unsafe {
+        Value::from(mri_sys::rb_funcallv(receiver.0, method_id, 1, &argument.0))
+    });
+    bench("Value::send_unprotected", raw, || unsafe { receiver.send_unprotected("+", &[argument]) });
+    bench("Value::send", raw, || receiver.send("+", &[argument]).unwrap());
+    bench("catch_unwind(rb_funcallv)", raw, || {
+        mri_sys::helpers::catch_unwind(|| unsafe {
+            Value::from(mri_sys::rb_funcallv(receiver.0, method_id, 1, &argument.0))
+        }).unwrap()
+    });
+
+    let object = classes::Object();
+    let constant_id = unsafe { mri_sys::rb_intern(b"Integer\0".as_ptr() as _) };
+    let raw = bench("rb_const_get", 0.0, || unsafe { Value::from(mri_sys::rb_const_get(object.0, constant_id)) });
+    bench("Value::constant_unprotected", raw, || unsafe { object.constant_unprotected("Integer") });
+    bench("Value::constant", raw, || object.constant("Integer").unwrap());
+
+    vm.shutdown();
+}
+
+/// Prints and returns the time per call, relative to `raw` unless that is zero.
+fn bench<F>(name: &str, raw: f64, mut f: F) -> f64
+    where F: FnMut() -> Value {
+    let start = Instant::now();
+
+    for _ in 0..ITERATIONS {
+        std::hint::black_box(f());
+    }
+
+    let nanos = start.elapsed().as_nanos() as f64 / ITERATIONS as f64;
+    if raw > 0.0 {
+        println!("{:<28} {:>8.1} ns/iter {:>6.2}x", name, nanos, nanos / raw);
+    } else {
+        println!("{:<28} {:>8.1} ns/iter", name, nanos);
+    }
+
+    nanos
+}
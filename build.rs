@@ -12,22 +12,43 @@ const LINK_CRYPT: bool = true;
 const LINK_CRYPT: bool = false;
 
 fn main() {
+    let link_kind = match std::env::var("RUBY_LINK_KIND") {
+        Ok(kind) => kind,
+        Err(..) => "dylib".to_owned(),
+    };
+
     let ruby_lib_name = match std::env::var("RUBY_LIB") {
         Ok(lib) => lib,
+        // The static library is called `libruby-static.a` by default.
+        Err(..) if link_kind == "static" => "ruby-static".to_owned(),
         Err(..) => "ruby".to_owned(),
     };
 
-    println!("cargo:rustc-link-lib=dylib={}", ruby_lib_name);
+    println!("cargo:rustc-link-lib={}={}", link_kind, ruby_lib_name);
+    println!("cargo:rustc-env=MRI_SYS_RUBY_LIB={}", ruby_lib_name);
+    println!("cargo:rustc-env=MRI_SYS_LINK_KIND={}", link_kind);
+
+    let ruby_version = current_ruby_version();
+
+    if let Some(RubyVersion(major, minor)) = ruby_version {
+        println!("cargo:rustc-env=MRI_SYS_RUBY_VERSION={}.{}", major, minor);
+    }
+
+    println!("cargo:rustc-check-cfg=cfg(mri_has_funcallv_kw)");
+    if ruby_version_supports_funcallv_kw(ruby_version) {
+        println!("cargo:rustc-cfg=mri_has_funcallv_kw");
+    }
 
     if LINK_CRYPT { println!("cargo:rustc-link-lib=dylib=crypt"); }
 
-    if should_use_flonum() {
+    println!("cargo:rustc-check-cfg=cfg(mri_use_flonum)");
+    if should_use_flonum(ruby_version) {
         println!("cargo:rustc-cfg=mri_use_flonum");
     }
 }
 
 /// Logic taken from MRI's `ruby/ruby.h`.
-fn should_use_flonum() -> bool {
+fn should_use_flonum(ruby_version: Option<RubyVersion>) -> bool {
     const SIZEOF_LONG: usize = mem::size_of::<libc::c_long>();
     const SIZEOF_LONG_LONG: usize = mem::size_of::<libc::c_longlong>();
     const SIZEOF_VOIDP: usize = mem::size_of::<*const libc::c_void>();
@@ -41,16 +62,24 @@ fn should_use_flonum() -> bool {
         panic!("error: ruby requires sizeof(void*) == sizeof(long) or sizeof(LONG_LONG) to be compiled");
     };
 
-    ruby_version_supports_flonum() && sizeof_value >= SIZEOF_DOUBLE
+    ruby_version_supports_flonum(ruby_version) && sizeof_value >= SIZEOF_DOUBLE
 }
 
-fn ruby_version_supports_flonum() -> bool {
+fn ruby_version_supports_flonum(ruby_version: Option<RubyVersion>) -> bool {
     const FIRST_RUBY_VERSION_WITH_FLONUM: RubyVersion = RubyVersion(2, 0);
 
-    current_ruby_version().map(|current_version| current_version >= FIRST_RUBY_VERSION_WITH_FLONUM)
+    ruby_version.map(|current_version| current_version >= FIRST_RUBY_VERSION_WITH_FLONUM)
         .unwrap_or(true) // Optimistically assume the current Ruby version is >= 2.0.0.
 }
 
+/// `rb_funcallv_kw` was added when Ruby 2.7 began separating keyword arguments.
+fn ruby_version_supports_funcallv_kw(ruby_version: Option<RubyVersion>) -> bool {
+    const FIRST_RUBY_VERSION_WITH_FUNCALLV_KW: RubyVersion = RubyVersion(2, 7);
+
+    ruby_version.map(|current_version| current_version >= FIRST_RUBY_VERSION_WITH_FUNCALLV_KW)
+        .unwrap_or(true)
+}
+
 fn current_ruby_version() -> Option<RubyVersion>  {
     // Allow the user to explicitly specify the Ruby version.
     if let Some(version_str) = std::env::var("RUBY_VERSION").ok() {
@@ -1,9 +1,13 @@
 use std::io::Write;
 
 fn main() {
-    unsafe { mri_sys::ruby_init() };
+    let vm = mri_sys::helpers::VmBuilder::new()
+        .script_name("repl")
+        .arguments(std::env::args().skip(1))
+        .init()
+        .expect("could not initialise Ruby");
 
-    loop {
+    for line in 1.. {
         print!("cool-interpreter:8=====D -- ");
         std::io::stdout().flush().unwrap();
 
@@ -13,12 +17,12 @@ fn main() {
         match &input.trim().to_lowercase()[..] {
             "exit" | "quit" => break,
             "help" => println!("-> https://www.eapservices.co.nz/"),
-            _ => match mri_sys::helpers::eval(&input, mri_sys::helpers::Binding::top_level(), Some("mock-filename.rb")) {
+            _ => match mri_sys::helpers::eval(&input, mri_sys::helpers::Binding::top_level(), Some(mri_sys::helpers::SourceLocation::new("repl", line))) {
                 Ok(value) => println!("-> {:?}", value),
                 Err(e) => eprintln!("ERROR, EXCEPTION RAISED: {}", e),
             },
         }
     }
 
-    unsafe { mri_sys::ruby_cleanup(0) };
+    std::process::exit(vm.shutdown());
 }
@@ -0,0 +1,363 @@
+//! `#[derive(ToRuby, FromRuby)]` for `mri_sys::helpers`. Enable the `derive`
+//! feature of `mri-sys` rather than depending on this crate directly.
+//!
+//! * Structs with named fields become `Hash`es with symbol keys, or instances of a
+//!   Ruby `Struct` or `Data` class with `#[ruby(class = "Point")]`.
+//! * Tuple structs become `Array`s and unit structs become `nil`.
+//! * Enum variants without fields become symbols, like `:Red`. Other variants become
+//!   a `Hash` with the variant name as the only key, like `{ Rgb: [255, 0, 0] }`.
+//!
+//! Fields accept `#[ruby(rename = "name")]`, `#[ruby(default)]` to fall back to
+//! `Default::default()` when the key is missing and `#[ruby(skip)]` to leave them out
+//! altogether. Variants accept `#[ruby(rename = "name")]`.
+//!
+//! Fields whose key is missing are converted from `nil`, so `Option` fields are optional.
+//!
+//! `ToRuby` can't fail, so with `#[ruby(class = ..)]` the derived `to_ruby()` panics if
+//! the constant isn't defined or its `new` raises. Define the class before converting
+//! anything to it, and keep its `new` from validating the fields.
+
+extern crate proc_macro;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, LitStr};
+
+#[proc_macro_derive(ToRuby, attributes(ruby))]
+pub fn derive_to_ruby(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    to_ruby(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+#[proc_macro_derive(FromRuby, attributes(ruby))]
+pub fn derive_from_ruby(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    from_ruby(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// `#[ruby(..)]` options, wherever they appear.
+#[derive(Default)]
+struct Options {
+    rename: Option<String>,
+    class: Option<String>,
+    default: bool,
+    skip: bool,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Position { Container, Variant, Field }
+
+impl Options {
+    fn parse(attributes: &[syn::Attribute], position: Position) -> syn::Result<Self> {
+        let mut options = Options::default();
+
+        for attribute in attributes.iter().filter(|attribute| attribute.path().is_ident("ruby")) {
+            attribute.parse_nested_meta(|meta| {
+                let allowed = match () {
+                    _ if meta.path.is_ident("rename") => position != Position::Container,
+                    _ if meta.path.is_ident("class") => position == Position::Container,
+                    _ if meta.path.is_ident("default") || meta.path.is_ident("skip") => position == Position::Field,
+                    _ => false,
+                };
+
+                if !allowed {
+                    return Err(meta.error("unsupported ruby attribute here"));
+                }
+
+                if meta.path.is_ident("rename") {
+                    options.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("class") {
+                    options.class = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("default") {
+                    options.default = true;
+                } else {
+                    options.skip = true;
+                }
+
+                Ok(())
+            })?;
+        }
+
+        Ok(options)
+    }
+}
+
+/// A field that takes part in the conversion.
+struct Field {
+    /// How the field is accessed or bound, like `name` or `_0`.
+    binding: Ident,
+    /// The field name in Rust, or its index for tuple fields.
+    member: syn::Member,
+    /// The key in Ruby.
+    key: String,
+    options: Options,
+}
+
+fn fields(fields: &Fields) -> syn::Result<Vec<Field>> {
+    fields.iter().enumerate().map(|(index, field)| {
+        let options = Options::parse(&field.attrs, Position::Field)?;
+        let (binding, member) = match field.ident {
+            Some(ref ident) => (ident.clone(), syn::Member::Named(ident.clone())),
+            None => (format_ident!("_{}", index), syn::Member::Unnamed(index.into())),
+        };
+        let key = options.rename.clone().unwrap_or_else(|| match member {
+            syn::Member::Named(ref ident) => ident.to_string(),
+            syn::Member::Unnamed(ref index) => index.index.to_string(),
+        });
+
+        Ok(Field { binding, member, key, options })
+    }).collect()
+}
+
+fn check_class(options: &Options, fields: &Fields) -> syn::Result<()> {
+    match *fields {
+        Fields::Named(..) => Ok(()),
+        _ if options.class.is_none() => Ok(()),
+        _ => Err(syn::Error::new(Span::call_site(), "`class` is only supported on structs with named fields")),
+    }
+}
+
+fn support() -> TokenStream {
+    quote!(::mri_sys::helpers::derive_support)
+}
+
+fn impl_header(input: &DeriveInput, trait_name: &str) -> TokenStream {
+    let name = &input.ident;
+    let trait_path: syn::Path = syn::parse_str(&format!("::mri_sys::helpers::{}", trait_name)).unwrap();
+    let mut generics = input.generics.clone();
+
+    for parameter in generics.type_params_mut() {
+        parameter.bounds.push(syn::parse_quote!(#trait_path));
+    }
+
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+    quote!(impl #impl_generics #trait_path for #name #type_generics #where_clause)
+}
+
+fn to_ruby(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let support = support();
+    let options = Options::parse(&input.attrs, Position::Container)?;
+    let header = impl_header(input, "ToRuby");
+
+    let body = match input.data {
+        Data::Struct(ref data) => {
+            check_class(&options, &data.fields)?;
+
+            let fields = fields(&data.fields)?;
+            let bindings = fields.iter().map(|field| &field.binding);
+            let members = fields.iter().map(|field| &field.member);
+            let name = &input.ident;
+            let conversion = fields_to_ruby(&data.fields, &fields, options.class.as_deref());
+
+            quote! {
+                #[allow(unused_variables)]
+                let #name { #(#members: ref #bindings,)* } = *self;
+                #conversion
+            }
+        },
+        Data::Enum(ref data) => {
+            if options.class.is_some() {
+                return Err(syn::Error::new(Span::call_site(), "`class` is only supported on structs"));
+            }
+
+            let arms = data.variants.iter().map(|variant| {
+                let name = &variant.ident;
+                let tag = Options::parse(&variant.attrs, Position::Variant)?.rename.unwrap_or_else(|| name.to_string());
+                let fields = fields(&variant.fields)?;
+                let bindings = fields.iter().map(|field| &field.binding);
+                let members = fields.iter().map(|field| &field.member);
+                let conversion = fields_to_ruby(&variant.fields, &fields, None);
+
+                let value = match variant.fields {
+                    Fields::Unit => quote!(#support::symbol(#tag)),
+                    _ => quote!(#support::hash(&[(#tag, #conversion)])),
+                };
+
+                Ok(quote! {
+                    #[allow(unused_variables)]
+                    Self::#name { #(#members: ref #bindings,)* .. } => #value,
+                })
+            }).collect::<syn::Result<Vec<_>>>()?;
+
+            quote!(match *self { #(#arms)* })
+        },
+        Data::Union(ref data) => return Err(syn::Error::new_spanned(data.union_token, "unions can't be converted to Ruby")),
+    };
+
+    Ok(quote! {
+        #header {
+            fn to_ruby(&self) -> ::mri_sys::helpers::Value {
+                #body
+            }
+        }
+    })
+}
+
+/// Converts bound fields to a `Hash`, `Array`, class instance or `nil`.
+fn fields_to_ruby(shape: &Fields, fields: &[Field], class: Option<&str>) -> TokenStream {
+    let support = support();
+    let fields: Vec<_> = fields.iter().filter(|field| !field.options.skip).collect();
+    let keys = fields.iter().map(|field| &field.key);
+    let values: Vec<_> = fields.iter().map(|field| {
+        let binding = &field.binding;
+        quote!(#support::to_ruby(#binding))
+    }).collect();
+
+    match (shape, class) {
+        (Fields::Unit, _) => quote!(::mri_sys::helpers::Value::NIL),
+        (_, Some(class)) => quote!(#support::new_instance(#class, &[#(#values),*])),
+        (Fields::Named(..), None) => quote!(#support::hash(&[#((#keys, #values)),*])),
+        (Fields::Unnamed(..), None) => quote!(#support::array(&[#(#values),*])),
+    }
+}
+
+fn from_ruby(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let support = support();
+    let options = Options::parse(&input.attrs, Position::Container)?;
+    let header = impl_header(input, "FromRuby");
+    let type_name = input.ident.to_string();
+
+    let body = match input.data {
+        Data::Struct(ref data) => {
+            check_class(&options, &data.fields)?;
+
+            let fields = fields(&data.fields)?;
+            let construct = fields_from_ruby(quote!(Self), &type_name, &data.fields, &fields, options.class.as_deref());
+
+            quote!(::std::result::Result::Ok(#construct))
+        },
+        Data::Enum(ref data) => {
+            if options.class.is_some() {
+                return Err(syn::Error::new(Span::call_site(), "`class` is only supported on structs"));
+            }
+
+            let mut unit_arms = Vec::new();
+            let mut tagged_arms = Vec::new();
+
+            for variant in data.variants.iter() {
+                let name = &variant.ident;
+                let tag = Options::parse(&variant.attrs, Position::Variant)?.rename.unwrap_or_else(|| name.to_string());
+                let fields = fields(&variant.fields)?;
+                let variant_type_name = format!("{}::{}", type_name, name);
+                let construct = fields_from_ruby(quote!(Self::#name), &variant_type_name, &variant.fields, &fields, None);
+
+                match variant.fields {
+                    Fields::Unit => unit_arms.push(quote!(#tag => ::std::result::Result::Ok(Self::#name),)),
+                    _ => tagged_arms.push(quote!(#tag => {
+                        let value = payload;
+                        ::std::result::Result::Ok(#construct)
+                    })),
+                }
+            }
+
+            quote! {
+                if let ::std::option::Option::Some(tag) = #support::symbol_name(value)? {
+                    return match tag.as_str() {
+                        #(#unit_arms)*
+                        other => ::std::result::Result::Err(#support::unknown_variant(#type_name, other)),
+                    };
+                }
+
+                #[allow(unused_variables)]
+                let (tag, payload) = #support::tagged(value, #type_name)?;
+
+                match tag.as_str() {
+                    #(#tagged_arms)*
+                    other => ::std::result::Result::Err(#support::unknown_variant(#type_name, other)),
+                }
+            }
+        },
+        Data::Union(ref data) => return Err(syn::Error::new_spanned(data.union_token, "unions can't be converted from Ruby")),
+    };
+
+    Ok(quote! {
+        #header {
+            fn from_ruby(value: ::mri_sys::helpers::Value)
+                -> ::std::result::Result<Self, ::mri_sys::helpers::CaughtException> {
+                #body
+            }
+        }
+    })
+}
+
+/// Builds `constructor { .. }` or `constructor(..)` out of `value`, returning early on errors.
+fn fields_from_ruby(
+    constructor: TokenStream,
+    type_name: &str,
+    shape: &Fields,
+    fields: &[Field],
+    class: Option<&str>,
+) -> TokenStream {
+    let support = support();
+    let convert = |field: &Field, value: TokenStream| {
+        let key = &field.key;
+        quote! {
+            ::mri_sys::helpers::FromRuby::from_ruby(#value)
+                .map_err(|e| #support::in_field(e, #type_name, #key))?
+        }
+    };
+
+    match *shape {
+        Fields::Unit => quote!({
+            <() as ::mri_sys::helpers::FromRuby>::from_ruby(value)?;
+            #constructor
+        }),
+        Fields::Named(..) => {
+            let initializers = fields.iter().map(|field| {
+                let member = &field.member;
+                let key = &field.key;
+                let from_value = convert(field, quote!(value));
+
+                let missing = if field.options.default {
+                    quote!(::std::default::Default::default())
+                } else {
+                    quote! {
+                        match ::mri_sys::helpers::FromRuby::from_ruby(::mri_sys::helpers::Value::NIL) {
+                            ::std::result::Result::Ok(value) => value,
+                            ::std::result::Result::Err(..) => return ::std::result::Result::Err(#support::missing_field(#type_name, #key)),
+                        }
+                    }
+                };
+
+                if field.options.skip {
+                    quote!(#member: ::std::default::Default::default())
+                } else {
+                    quote! {
+                        #member: match #support::get(hash, #key)? {
+                            ::std::option::Option::Some(value) => #from_value,
+                            ::std::option::Option::None => #missing,
+                        }
+                    }
+                }
+            });
+            let class = match class {
+                Some(class) => quote!(::std::option::Option::Some(#class)),
+                None => quote!(::std::option::Option::None),
+            };
+
+            quote!({
+                let hash = #support::expect_hash(value, #class)?;
+                #constructor { #(#initializers,)* }
+            })
+        },
+        Fields::Unnamed(..) => {
+            let length = fields.iter().filter(|field| !field.options.skip).count();
+            let mut index = 0usize;
+            let initializers: Vec<_> = fields.iter().map(|field| {
+                if field.options.skip {
+                    quote!(::std::default::Default::default())
+                } else {
+                    let value = convert(field, quote!(elements[#index]));
+                    index += 1;
+                    value
+                }
+            }).collect();
+
+            quote!({
+                #[allow(unused_variables)]
+                let elements = #support::expect_array(value, #length, #type_name)?;
+                #constructor(#(#initializers),*)
+            })
+        },
+    }
+}
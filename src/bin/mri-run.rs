@@ -0,0 +1,7 @@
+//! A drop-in `ruby` executable built on `mri-sys`.
+//!
+//! Pass `--mri-sys-info` to print what was detected about Ruby at build time.
+
+fn main() {
+    std::process::exit(mri_sys::helpers::launcher::main());
+}
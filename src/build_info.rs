@@ -0,0 +1,13 @@
+//! What the build script detected about the Ruby this crate was built against.
+
+/// The `major.minor` Ruby version, if it was detected or given via `RUBY_VERSION`.
+pub const RUBY_VERSION: Option<&str> = option_env!("MRI_SYS_RUBY_VERSION");
+
+/// The name of the linked Ruby library, set via `RUBY_LIB`.
+pub const RUBY_LIB: &str = env!("MRI_SYS_RUBY_LIB");
+
+/// How the Ruby library is linked, `dylib` unless overridden via `RUBY_LINK_KIND`.
+pub const LINK_KIND: &str = env!("MRI_SYS_LINK_KIND");
+
+/// Whether floats are encoded as flonums in `VALUE`.
+pub const USE_FLONUM: bool = cfg!(mri_use_flonum);
@@ -1,12 +1,20 @@
 use libc;
 use super::*;
 
+/// An unblocking function, called by Ruby from another thread to interrupt a
+/// function running without the GVL.
+#[allow(non_camel_case_types)]
+pub type rb_unblock_function_t = extern "C" fn(*mut libc::c_void);
+
 #[link(name = "gmp")]
 #[cfg_attr(target_os = "macos", link(name = "CoreFoundation", kind = "framework"))]
 extern "C" {
     pub fn ruby_init();
+    /// Tells Ruby where the native stack of the current thread starts.
+    pub fn ruby_init_stack(addr: *mut VALUE);
     pub fn ruby_setup() -> libc::c_int;
-    pub fn ruby_cleanup(_: libc::c_int);
+    /// Cleans up the interpreter, running `at_exit` handlers, and returns the exit status.
+    pub fn ruby_cleanup(ex: libc::c_int) -> libc::c_int;
 
     pub fn rb_eval_string(_: *const libc::c_char) -> VALUE;
     pub fn rb_eval_string_protect(_: *const libc::c_char, _: *mut libc::c_int) -> VALUE;
@@ -24,6 +32,11 @@ extern "C" {
     /// Calls your C function with the value specified in `args`. If the C function does not
     /// escape, sets `state` to `0`.
     pub fn rb_protect(f: extern "C" fn(VALUE) -> VALUE, args: VALUE, state: *mut libc::c_int) -> VALUE;
+    /// Continues a global escape previously caught by `rb_protect`, given its `state`.
+    pub fn rb_jump_tag(state: libc::c_int) -> !;
+
+    pub fn rb_exc_raise(exception: VALUE) -> !;
+    pub fn rb_exc_new_str(klass: VALUE, message: VALUE) -> VALUE;
 
     /// Convert a C string to a symbol (adding to the symbol table).
     pub fn rb_intern(_: *const libc::c_char) -> ID;
@@ -38,9 +51,13 @@ extern "C" {
     pub fn rb_to_symbol(name: VALUE) -> VALUE;
     pub fn rb_check_symbol(namep: *mut VALUE) -> VALUE;
     pub fn rb_id2sym(_: ID) -> VALUE;
+    pub fn rb_sym2id(_: VALUE) -> ID;
 
     pub fn rb_class2name(_: VALUE) -> *const libc::c_char;
     pub fn rb_obj_classname(_: VALUE) -> *const libc::c_char;
+    pub fn rb_obj_class(_: VALUE) -> VALUE;
+    /// Gets the superclass, skipping included modules. `nil` for `BasicObject`.
+    pub fn rb_class_superclass(_: VALUE) -> VALUE;
 
     /// Call a Ruby function using varargs to pass the arguments.
     pub fn rb_funcall(receiver: VALUE, name: ID, argc: libc::c_int, ...) -> VALUE;
@@ -50,6 +67,11 @@ extern "C" {
     pub fn rb_funcallv_public(_: VALUE, _: ID, _: libc::c_int, _: *const VALUE) -> VALUE;
     pub fn rb_funcall_passing_block(_: VALUE, _: ID, _: libc::c_int, _: *const VALUE) -> VALUE;
     pub fn rb_funcall_with_block(_: VALUE, _: ID, _: libc::c_int, _: *const VALUE, _: VALUE) -> VALUE;
+    /// Call a Ruby function, treating the last element of `argv` as keywords if `kw_splat` is nonzero.
+    ///
+    /// Only available on Ruby 2.7 and newer.
+    #[cfg(mri_has_funcallv_kw)]
+    pub fn rb_funcallv_kw(_: VALUE, _: ID, _: libc::c_int, _: *const VALUE, kw_splat: libc::c_int) -> VALUE;
 
     /// Gets the value of a constant.
     pub fn rb_const_get(space: VALUE, name: ID) -> VALUE;
@@ -60,6 +82,10 @@ extern "C" {
     pub fn rb_string_value_cstr(_: *const VALUE) -> *const libc::c_char;
     /// Convert C string to a Ruby `String`.
     pub fn rb_str_new_cstr(ptr: *const libc::c_char) -> VALUE;
+    /// Convert a buffer of bytes to a binary Ruby `String`.
+    pub fn rb_str_new(ptr: *const libc::c_char, len: libc::c_long) -> VALUE;
+    /// Convert a buffer of UTF-8 to a Ruby `String`.
+    pub fn rb_utf8_str_new(ptr: *const libc::c_char, len: libc::c_long) -> VALUE;
 
     pub fn rb_define_class(_: *const libc::c_char, _: VALUE) -> VALUE;
     pub fn rb_define_module(_: *const libc::c_char) -> VALUE;
@@ -100,11 +126,25 @@ extern "C" {
     pub fn rb_gv_get(_: *const libc::c_char) -> VALUE;
     pub fn rb_iv_get(_: VALUE, _: *const libc::c_char) -> VALUE;
     pub fn rb_iv_set(_: VALUE, _: *const libc::c_char, _: VALUE) -> VALUE;
+    /// Gets an instance variable. Names without an `@` are hidden from Ruby code.
+    pub fn rb_ivar_get(_: VALUE, _: ID) -> VALUE;
+    pub fn rb_ivar_set(_: VALUE, _: ID, _: VALUE) -> VALUE;
+    pub fn rb_ivar_defined(_: VALUE, _: ID) -> VALUE;
+    /// Wraps a pointer in a `T_DATA` object, calling `dfree` on it once the object is
+    /// collected. A `klass` of zero makes the object hidden from Ruby.
+    pub fn rb_data_object_wrap(klass: VALUE,
+                               datap: *mut libc::c_void,
+                               dmark: Option<extern "C" fn(*mut libc::c_void)>,
+                               dfree: Option<extern "C" fn(*mut libc::c_void)>) -> VALUE;
 
     pub fn rb_equal(_: VALUE, _: VALUE) -> VALUE;
+    /// Checks if an object is an instance of a class or one of its descendants. Equivalent to `Object#kind_of?`.
+    pub fn rb_obj_is_kind_of(obj: VALUE, klass: VALUE) -> VALUE;
     pub fn rb_ruby_verbose_ptr() -> *mut VALUE;
     pub fn rb_ruby_debug_ptr() -> *mut VALUE;
 
+    /// Raises an exception, unwinding past any Rust frames without running their destructors.
+    /// The `helpers::RubyError` API raises only once Rust code has returned.
     pub fn rb_raise(_: VALUE, _: *const libc::c_char, ...) -> !;
     pub fn rb_fatal(_: *const libc::c_char, ...) -> !;
     pub fn rb_bug(_: *const libc::c_char, ...) -> !;
@@ -150,11 +190,16 @@ extern "C" {
 
     pub fn rb_throw(_: *const libc::c_char, _: VALUE) -> !;
     pub fn rb_throw_obj(_: VALUE, _: VALUE) -> !;
+    /// Raises `ArgumentError` for a method called with `argc` arguments that takes `min..=max`.
+    pub fn rb_error_arity(argc: libc::c_int, min: libc::c_int, max: libc::c_int) -> !;
 
     pub fn rb_p(_: VALUE);
 
     pub fn rb_require(_: *const libc::c_char) -> VALUE;
 
+    /// Finds the index of an encoding by name, loading it if necessary.
+    pub fn rb_enc_find_index(name: *const libc::c_char) -> libc::c_int;
+
     pub fn ruby_sysinit(argc: *mut libc::c_int, _: *mut *mut *mut libc::c_char);
     pub fn ruby_options(argc: libc::c_int, argv: *mut *mut libc::c_char) -> *mut libc::c_void;
     pub fn ruby_executable_node(n: *mut libc::c_void, status: *mut libc::c_int) -> libc::c_int;
@@ -211,8 +256,63 @@ extern "C" {
     pub fn rb_newobj_of(_: VALUE, _: VALUE) -> VALUE;
     pub fn rb_obj_setup(obj: VALUE, klass: VALUE, ty: VALUE) -> VALUE;
 
+    /// Convert a C `long long` to a Ruby `Integer`.
+    pub fn rb_ll2inum(_: libc::c_longlong) -> VALUE;
+    /// Convert a C `unsigned long long` to a Ruby `Integer`.
+    pub fn rb_ull2inum(_: libc::c_ulonglong) -> VALUE;
+
     pub fn rb_float_new(_: libc::c_double) -> VALUE;
     pub fn rb_float_new_in_heap(_: libc::c_double) -> VALUE;
+
+    /// Gets an element of an `Array`, or `nil` if out of bounds.
+    pub fn rb_ary_entry(ary: VALUE, offset: libc::c_long) -> VALUE;
+    /// Creates the two element array `[car, cdr]`.
+    pub fn rb_assoc_new(car: VALUE, cdr: VALUE) -> VALUE;
+    /// Creates an empty `Array` with room for `capacity` elements.
+    pub fn rb_ary_new_capa(capacity: libc::c_long) -> VALUE;
+    pub fn rb_ary_push(ary: VALUE, item: VALUE) -> VALUE;
+
+    pub fn rb_hash_new() -> VALUE;
+    pub fn rb_hash_aref(hash: VALUE, key: VALUE) -> VALUE;
+    pub fn rb_hash_aset(hash: VALUE, key: VALUE, value: VALUE) -> VALUE;
+
+    /// Enables the garbage collector. Returns `Qtrue` if it was previously disabled.
+    pub fn rb_gc_enable() -> VALUE;
+    /// Disables the garbage collector. Returns `Qtrue` if it was already disabled.
+    pub fn rb_gc_disable() -> VALUE;
+
+    /// Releases the GVL, calls `func(data1)` and then reacquires the GVL.
+    ///
+    /// If Ruby needs to interrupt the thread (e.g. `Thread#kill` or a signal) it calls
+    /// `ubf(data2)` from another thread, which must make `func` return promptly.
+    pub fn rb_thread_call_without_gvl(func: extern "C" fn(*mut libc::c_void) -> *mut libc::c_void,
+                                      data1: *mut libc::c_void,
+                                      ubf: Option<rb_unblock_function_t>,
+                                      data2: *mut libc::c_void) -> *mut libc::c_void;
+    /// Like `rb_thread_call_without_gvl`, but returns without calling `func` if
+    /// there are pending interrupts.
+    pub fn rb_thread_call_without_gvl2(func: extern "C" fn(*mut libc::c_void) -> *mut libc::c_void,
+                                       data1: *mut libc::c_void,
+                                       ubf: Option<rb_unblock_function_t>,
+                                       data2: *mut libc::c_void) -> *mut libc::c_void;
+    /// Checks whether the current thread is a Ruby thread holding the GVL.
+    pub fn ruby_thread_has_gvl_p() -> libc::c_int;
+    pub fn rb_thread_current() -> VALUE;
+    /// Reacquires the GVL from within a function called by `rb_thread_call_without_gvl`.
+    pub fn rb_thread_call_with_gvl(func: extern "C" fn(*mut libc::c_void) -> *mut libc::c_void,
+                                   data1: *mut libc::c_void) -> *mut libc::c_void;
+}
+
+/// The builtin unblocking function for blocking IO, equivalent to `RUBY_UBF_IO`.
+#[allow(non_snake_case)]
+pub fn RUBY_UBF_IO() -> rb_unblock_function_t {
+    unsafe { std::mem::transmute(!0 as libc::uintptr_t) }
+}
+
+/// The builtin unblocking function for waiting on processes, equivalent to `RUBY_UBF_PROCESS`.
+#[allow(non_snake_case)]
+pub fn RUBY_UBF_PROCESS() -> rb_unblock_function_t {
+    unsafe { std::mem::transmute(!0 as libc::uintptr_t) }
 }
 
 #[allow(non_snake_case)]
@@ -2,13 +2,52 @@
 //!
 //! Turn if off with `default-features = false` for this crate in your manifest.
 
-// TODO:
-//   - integer to ruby
-//   - ruby to integer?
-//   - line numbers passed to eval func
-
-pub use self::protect::{catch_unwind, CaughtException};
+pub use self::convert::{FromRuby, ToRuby};
+#[cfg(feature = "derive")]
+pub use mri_sys_derive::{FromRuby, ToRuby};
+#[doc(hidden)]
+pub use self::convert::derive_support;
+pub use self::error::{ruby_callback, ExceptionHierarchy, IntoRubyError, RubyError};
+pub use self::error_kind::RubyErrorKind;
+pub use self::gvl::{without_gvl, Cancel};
+pub use self::protect::{catch_unwind, protect, record_throw_values, rust_panic_class, CaughtException, Frame, Unwind};
+pub use self::syntax::{check_syntax, SyntaxDiagnostic};
 pub use self::value::Value;
+pub use self::vm::{run_main, run_main_with, InitError, Verbosity, Vm, VmBuilder};
+use self::vm::debug_check;
+
+pub mod ast;
+pub mod branded;
+pub mod executor;
+pub mod gc;
+pub mod iseq;
+pub mod launcher;
+#[cfg(feature = "serde")]
+pub mod serde;
+mod convert;
+mod error;
+mod error_kind;
+mod gvl;
+mod syntax;
+mod vm;
+
+/// Proof that the current thread is running an initialised interpreter.
+///
+/// Borrowed from a `Vm`, or handed to closures that run on the `executor` thread.
+/// It is not `Send`, so it cannot leave that thread.
+pub struct Ruby {
+    _not_send: ::std::marker::PhantomData<*const ()>,
+}
+
+impl Ruby {
+    /// Creates a token for the current thread.
+    ///
+    /// The current thread must have called `ruby_init` and the interpreter must not
+    /// have been cleaned up yet.
+    pub unsafe fn assume_initialized() -> Self {
+        Ruby { _not_send: ::std::marker::PhantomData }
+    }
+}
 
 /// A binding is basically an execution context.
 /// Variables and classes defined inside a binding are only
@@ -28,19 +67,77 @@ impl Binding {
     pub fn allocate() -> Self {
         unsafe { Binding(std::modules::Kernel().send_unprotected("binding", &[])) }
     }
+
+    /// Creates a fresh binding whose `self` is a new, empty `Object`.
+    ///
+    /// Local variables defined in it are not visible to any other binding, so it
+    /// suits evaluating snippets that must not leak into each other or `TOPLEVEL_BINDING`.
+    pub fn isolated() -> Self {
+        unsafe {
+            let receiver = std::classes::Object().send_unprotected("new", &[]);
+            Binding(receiver.send_unprotected("instance_eval", &[*to_ruby::string("binding")]))
+        }
+    }
+
+    /// Gets the value of a local variable. Equivalent to `Binding#local_variable_get`.
+    pub fn local_variable_get(&self, name: &str) -> Result<Value, CaughtException> {
+        self.0.send("local_variable_get", &[*to_ruby::string(name)])
+    }
+
+    /// Sets a local variable, defining it if needed. Equivalent to `Binding#local_variable_set`.
+    pub fn local_variable_set(&self, name: &str, value: Value) -> Result<(), CaughtException> {
+        self.0.send("local_variable_set", &[*to_ruby::string(name), value]).map(|_| ())
+    }
+
+    /// Checks if a local variable is defined. Equivalent to `Binding#local_variable_defined?`.
+    pub fn local_variable_defined(&self, name: &str) -> Result<bool, CaughtException> {
+        self.0.send("local_variable_defined?", &[*to_ruby::string(name)]).map(|defined| crate::RTEST(defined.0))
+    }
+
+    /// Gets the names of all local variables. Equivalent to `Binding#local_variables`.
+    pub fn local_variables(&self) -> Result<Vec<String>, CaughtException> {
+        self.0.send("local_variables", &[])?.array_elements()?
+            .iter().map(Value::to_s).collect()
+    }
+
+    /// Gets the value of `self` in this binding. Equivalent to `Binding#receiver`.
+    pub fn receiver(&self) -> Result<Value, CaughtException> {
+        self.0.send("receiver", &[])
+    }
+
+    /// Gets the file and line this binding was created at. Equivalent to `Binding#source_location`.
+    ///
+    /// Fails rather than panicking if Ruby doesn't return a `[file, line]` pair.
+    pub fn source_location(&self) -> Result<(String, u32), CaughtException> {
+        self.0.send("source_location", &[])?.to()
+    }
+}
+
+/// Where evaluated code came from, as reported in backtraces, `__FILE__` and `__LINE__`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SourceLocation<'a> {
+    pub file: &'a str,
+    /// The line number of the first line of code, starting at 1.
+    pub line: u32,
+}
+
+impl<'a> SourceLocation<'a> {
+    /// Creates a location pointing at the given line of a file.
+    pub fn new(file: &'a str, line: u32) -> Self {
+        SourceLocation { file, line }
+    }
 }
 
 /// Evaluate Ruby code in a specific binding.
 ///
 /// Rescues all Ruby exceptions.
-// TODO: implement line numbers
 pub fn eval(
     ruby_code: &str,
     binding: Binding,
-    filename_for_debugging: Option<&str>,
+    location: Option<SourceLocation>,
 ) -> Result<Value, CaughtException> {
     crate::helpers::catch_unwind(|| unsafe {
-        eval_unprotected(ruby_code, binding, filename_for_debugging)
+        eval_unprotected(ruby_code, binding, location)
     })
 }
 
@@ -49,22 +146,50 @@ pub fn eval(
 pub unsafe fn eval_unprotected(
     ruby_code: &str,
     binding: Binding,
-    filename_for_debugging: Option<&str>,
+    location: Option<SourceLocation>,
 ) -> Value {
     let code_string = crate::helpers::to_ruby::string(ruby_code);
-    let filename = filename_for_debugging.map(crate::helpers::to_ruby::string);
-    // let line_number = crate::Qnil; // the argument is optional..but how to construct ruby int from rust?
 
     let mut argv = vec![
         *code_string,
     ];
 
-    if let Some(filename) = filename { argv.push(*filename) };
-    // if let Some(line_number) = line_number { argv.push(*line_number) };
+    if let Some(location) = location {
+        argv.push(*crate::helpers::to_ruby::string(location.file));
+        argv.push(crate::helpers::to_ruby::integer(location.line as i64));
+    }
 
     binding.0.send_unprotected("eval", &argv[..])
 }
 
+/// Evaluates a Ruby source file in a specific binding.
+///
+/// Backtraces point at the file's real path. Rescues all Ruby exceptions,
+/// including errors reading the file.
+pub fn eval_file(
+    path: impl AsRef<::std::path::Path>,
+    binding: Binding,
+) -> Result<Value, CaughtException> {
+    let path = path_to_ruby(path.as_ref());
+    let real_path = std::classes::File().send("realpath", &[path])?;
+    let code_string = std::classes::File().send("read", &[real_path])?;
+
+    binding.0.send("eval", &[code_string, real_path, to_ruby::integer(1)])
+}
+
+/// Passes the raw bytes of the path, so paths that aren't UTF-8 are kept as they are.
+#[cfg(unix)]
+fn path_to_ruby(path: &::std::path::Path) -> Value {
+    use ::std::os::unix::ffi::OsStrExt;
+
+    to_ruby::bytes(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn path_to_ruby(path: &::std::path::Path) -> Value {
+    *to_ruby::string(&path.to_string_lossy())
+}
+
 /// Converting Rust values to Ruby values.
 pub mod to_ruby {
     use super::Value;
@@ -78,6 +203,8 @@ pub mod to_ruby {
     /// Convert a Rust `&str` to a Ruby `String`
     pub fn string(string: &str)
         -> WrappedWithData<Value, std::ffi::CString>  {
+        super::debug_check();
+
         let cstring = std::ffi::CString::new(string).unwrap();
         let string_as_value = unsafe {
             crate::rb_str_new_cstr(cstring.as_ptr())
@@ -85,13 +212,38 @@ pub mod to_ruby {
 
         WrappedWithData {
             _data: cstring,
-            value: Value(string_as_value),
+            value: Value::from(string_as_value),
         }
     }
 
+    /// Convert Rust bytes to a binary Ruby `String`
+    pub fn bytes(bytes: &[u8]) -> Value {
+        super::debug_check();
+
+        Value::from(unsafe { crate::rb_str_new(bytes.as_ptr() as _, bytes.len() as _) })
+    }
+
+    /// Convert a Rust IO error to the matching `Errno::*` exception, or to an `IOError`
+    /// if it didn't come from the operating system.
+    pub fn io_error(error: &std::io::Error) -> Value {
+        match error.raw_os_error() {
+            Some(errno) => Value::from(unsafe { crate::rb_syserr_new(errno, std::ptr::null()) }),
+            None => Value::from(unsafe { crate::rb_exc_new_str(crate::rb_eIOError, string(&error.to_string()).0) }),
+        }
+    }
+
+    /// Convert a Rust integer to a Ruby `Integer`
+    pub fn integer(integer: i64) -> Value {
+        super::debug_check();
+
+        Value::from(unsafe { crate::rb_ll2inum(integer) })
+    }
+
     /// Convert a Rust `&str` to a Ruby `ID` / symbol
     pub fn symbol(string: &str)
         -> WrappedWithData<crate::ID, std::ffi::CString>  {
+        super::debug_check();
+
         let cstring = std::ffi::CString::new(string).unwrap();
         let string_as_value = unsafe {
             crate::rb_intern(cstring.as_ptr())
@@ -118,6 +270,19 @@ pub mod to_ruby {
 mod protect {
     use super::{to_ruby, Value};
     use crate::VALUE;
+    use std::any::Any;
+    use std::cell::Cell;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    thread_local! {
+        /// How many `protect` calls are running on this thread. `throw_hook` only records
+        /// values while one is, as otherwise nothing would ever take them.
+        static PROTECT_DEPTH: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Set once `record_throw_values` has installed `throw_hook`.
+    static RECORDING_THROWS: AtomicBool = AtomicBool::new(false);
 
     /// Wraps a Ruby exception `Value` and exposes its values usable in Rust.
     #[derive(Debug)]
@@ -125,14 +290,27 @@ mod protect {
         pub exception_object: Value,
         pub exception_class_name: String,
         pub message: String,
+        cause: Option<Box<CaughtException>>,
+    }
+
+    /// A single line of a Ruby backtrace.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Frame {
+        pub path: String,
+        pub lineno: u32,
+        /// The method or block the line is in, like `block in Foo#bar`.
+        pub label: String,
     }
 
+    /// How many causes deep `CaughtException` follows `Exception#cause`.
+    const MAX_CAUSE_DEPTH: usize = 16;
+
     /// Compare the class and message of an exception against another.
     impl PartialEq for CaughtException {
         fn eq(&self, rhs: &Self) -> bool {
             let CaughtException {
                 ref exception_class_name, ref message,
-                exception_object: _,
+                exception_object: _, cause: _,
             } = *self;
 
             *exception_class_name == rhs.exception_class_name &&
@@ -142,74 +320,461 @@ mod protect {
 
     impl Eq for CaughtException { }
 
+    /// How a protected call escaped, one variant per `rb_protect` state (`TAG_*` in `vm_core.h`).
+    ///
+    /// Only `Raise` clears the interpreter's error info. Every other variant leaves
+    /// Ruby mid-jump, so it should be passed on with `resume` before calling back into Ruby.
+    #[derive(Debug)]
+    pub enum Unwind {
+        /// `return` out of a method or lambda.
+        Return(Value),
+        /// `break` out of a block.
+        Break(Value),
+        /// `next` out of a block.
+        Next(Value),
+        /// `retry` inside a `rescue` clause.
+        Retry,
+        /// `redo` inside a block.
+        Redo,
+        /// An exception was raised.
+        Raise(CaughtException),
+        /// `throw` to a `catch` outside the call. Resuming delivers the value to the `catch`.
+        ///
+        /// Ruby hands the value straight to the `catch`, so `value` is only known after
+        /// `record_throw_values`, and never for throws from C via `rb_throw_obj`.
+        Throw { tag: Value, value: Option<Value> },
+        /// The thread is being killed or the interpreter hit a fatal error.
+        Fatal,
+        /// A state this crate doesn't know about.
+        Unknown(libc::c_int),
+    }
+
+    const TAG_RETURN: libc::c_int = 0x1;
+    const TAG_BREAK: libc::c_int = 0x2;
+    const TAG_NEXT: libc::c_int = 0x3;
+    const TAG_RETRY: libc::c_int = 0x4;
+    const TAG_REDO: libc::c_int = 0x5;
+    const TAG_RAISE: libc::c_int = 0x6;
+    const TAG_THROW: libc::c_int = 0x7;
+    const TAG_FATAL: libc::c_int = 0x8;
+
+    /// The internal `T_IMEMO` type of the `THROW_DATA` Ruby keeps in its error info
+    /// while unwinding for anything but an exception. Not part of `value_type`.
+    const T_IMEMO: libc::size_t = 0x1a;
+    /// `imemo_throw_data`, the kind of `T_IMEMO` stored in the flags above `FL_USHIFT`.
+    const IMEMO_THROW_DATA: libc::size_t = 3;
+    const IMEMO_MASK: libc::size_t = 0x0f;
+    const FL_USHIFT: u32 = 12;
+
+    /// The instance variable of the current `Thread` holding the `[tag, value]` of the last
+    /// `throw`, where the garbage collector can see them. Without an `@` it is hidden from Ruby.
+    const THROWN_IVAR: &str = "__mri_sys_thrown";
+
+    /// The hidden instance variable of a `RustPanic` holding its panic payload.
+    const PANIC_IVAR: &str = "__mri_sys_panic";
+
+    impl Unwind {
+        /// The raw `rb_protect` state.
+        pub fn state(&self) -> libc::c_int {
+            match *self {
+                Unwind::Return(..) => TAG_RETURN,
+                Unwind::Break(..) => TAG_BREAK,
+                Unwind::Next(..) => TAG_NEXT,
+                Unwind::Retry => TAG_RETRY,
+                Unwind::Redo => TAG_REDO,
+                Unwind::Raise(..) => TAG_RAISE,
+                Unwind::Throw { .. } => TAG_THROW,
+                Unwind::Fatal => TAG_FATAL,
+                Unwind::Unknown(state) => state,
+            }
+        }
+
+        /// Continues the unwind in Ruby, as if the Rust frames in between were not there.
+        ///
+        /// Must be called from a function Ruby called, after every Rust value that needs
+        /// dropping is gone, because Ruby unwinds with `longjmp`.
+        pub unsafe fn resume(self) -> ! {
+            match self {
+                Unwind::Raise(exception) => crate::rb_exc_raise(exception.exception_object.0),
+                Unwind::Throw { tag, value: Some(value) } => {
+                    // Hands the value on to the next `protect` out.
+                    set_thrown(crate::rb_assoc_new(tag.0, value.0));
+                    crate::rb_jump_tag(TAG_THROW)
+                },
+                unwind => crate::rb_jump_tag(unwind.state()),
+            }
+        }
+
+        /// Turns the unwind into an exception, abandoning any non-exception jump.
+        ///
+        /// `throw`, `break` and friends become a `LocalJumpError` because they cannot
+        /// pass through Rust code that isn't going to resume them.
+        pub fn into_exception(self) -> CaughtException {
+            let description = match self {
+                Unwind::Raise(exception) => return exception,
+                Unwind::Return(..) => "unexpected return".to_owned(),
+                Unwind::Break(..) => "break from proc-closure".to_owned(),
+                Unwind::Next(..) => "unexpected next".to_owned(),
+                Unwind::Retry => "retry outside of rescue clause".to_owned(),
+                Unwind::Redo => "unexpected redo".to_owned(),
+                Unwind::Throw { tag, .. } => uncaught_throw(tag),
+                Unwind::Fatal => "fatal error".to_owned(),
+                Unwind::Unknown(state) => format!("unknown unwind state {}", state),
+            };
+            let class = match self {
+                Unwind::Fatal => unsafe { crate::rb_eFatal },
+                _ => unsafe { crate::rb_eLocalJumpError },
+            };
+
+            unsafe {
+                crate::rb_set_errinfo(crate::Qnil); // abandon the jump
+
+                let exception = Value::from(crate::rb_exc_new_str(class, to_ruby::string(&description).0));
+                CaughtException::from_exception(exception)
+            }
+        }
+    }
+
+    impl CaughtException {
+        /// Wraps an exception that is not being raised.
+        pub(crate) fn from_exception(exception_object: Value) -> Self {
+            CaughtException::with_causes(exception_object, MAX_CAUSE_DEPTH)
+        }
+
+        /// Reads the exception with protected calls, so that an exception whose `#message`
+        /// or `#cause` raises falls back to its `#inspect` or class name instead.
+        fn with_causes(exception_object: Value, max_depth: usize) -> Self {
+            let exception_class_name = exception_object.object_class_name();
+            let message = exception_object.send("message", &[])
+                .and_then(Value::to_s_lossy)
+                .or_else(|_| exception_object.inspect().and_then(Value::to_s_lossy))
+                .unwrap_or_else(|_| exception_class_name.clone());
+            let cause = match exception_object.send("cause", &[]) {
+                Ok(cause) if !cause.is_nil() && max_depth > 0 => {
+                    Some(Box::new(CaughtException::with_causes(cause, max_depth - 1)))
+                },
+                _ => None,
+            };
+
+            CaughtException { exception_object, exception_class_name, message, cause }
+        }
+
+        /// The exception that was being handled when this one was raised.
+        /// Equivalent to `Exception#cause`.
+        pub fn cause(&self) -> Option<&CaughtException> {
+            self.cause.as_deref()
+        }
+
+        /// Checks if the exception is an instance of the class or one of its subclasses.
+        /// Equivalent to `Exception#is_a?`.
+        pub fn is_a(&self, class: Value) -> bool {
+            crate::RTEST(unsafe { crate::rb_obj_is_kind_of(self.exception_object.0, class.0) })
+        }
+
+        /// Converts a `SystemCallError`, such as `Errno::ENOENT`, to an IO error with the
+        /// same OS error code. `None` for any other exception.
+        pub fn as_io_error(&self) -> Option<std::io::Error> {
+            if !self.is_a(Value::from(unsafe { crate::rb_eSystemCallError })) {
+                return None;
+            }
+
+            let errno = self.exception_object.send("errno", &[]).ok()?;
+            if errno.is_nil() { return None }
+
+            errno.to_i64().ok().map(|errno| std::io::Error::from_raw_os_error(errno as i32))
+        }
+
+        /// The backtrace, innermost frame first. Empty if the exception was never raised.
+        pub fn backtrace(&self) -> Vec<Frame> {
+            self.backtrace_locations().unwrap_or_else(|| self.parsed_backtrace())
+        }
+
+        /// Formats the exception the way Ruby prints uncaught exceptions, without terminal
+        /// highlighting. Equivalent to `Exception#full_message(highlight: false)`.
+        pub fn full_message(&self) -> String {
+            let keywords = Value::from(unsafe { crate::rb_hash_new() });
+            unsafe { crate::rb_hash_aset(keywords.0, symbol("highlight"), crate::Qfalse) };
+
+            self.exception_object.send_with_keywords("full_message", &[], keywords)
+                .and_then(|message| message.to_s()).unwrap_or_else(|_| self.to_string())
+        }
+
+        /// Reads `Exception#backtrace_locations`, which is `nil` when the backtrace was
+        /// set by hand.
+        fn backtrace_locations(&self) -> Option<Vec<Frame>> {
+            let locations = self.exception_object.send("backtrace_locations", &[]).ok()?;
+            if locations.is_nil() { return None }
+
+            locations.array_elements().ok()?.into_iter().map(|location| {
+                Some(Frame {
+                    path: location.send("path", &[]).ok()?.to_s().ok()?,
+                    lineno: location.send("lineno", &[]).ok()?.to_i64().ok()? as u32,
+                    label: location.send("label", &[]).ok()?.to_s().ok()?,
+                })
+            }).collect()
+        }
+
+        /// Parses `Exception#backtrace` lines like `script.rb:12:in 'Foo#bar'`.
+        fn parsed_backtrace(&self) -> Vec<Frame> {
+            let lines = self.exception_object.send("backtrace", &[])
+                .and_then(|backtrace| if backtrace.is_nil() { Ok(Vec::new()) } else { backtrace.array_elements() })
+                .unwrap_or_default();
+
+            lines.into_iter().filter_map(|line| {
+                let line = line.to_s().ok()?;
+                let (location, label) = line.split_once(":in ").unwrap_or((&line, ""));
+                let (path, lineno) = location.rsplit_once(':')?;
+
+                Some(Frame {
+                    path: path.to_owned(),
+                    lineno: lineno.parse().ok()?,
+                    label: label.trim_matches(|c| c == '\'' || c == '`').to_owned(),
+                })
+            }).collect()
+        }
+    }
+
+    /// Describes a throw without calling back into Ruby, which mustn't happen mid-unwind.
+    fn uncaught_throw(tag: Value) -> String {
+        if tag.is_of_value_type(crate::value_type::T_SYMBOL) {
+            let name = unsafe { std::ffi::CStr::from_ptr(crate::rb_id2name(crate::rb_sym2id(tag.0))) };
+            format!("uncaught throw :{}", name.to_string_lossy())
+        } else {
+            format!("uncaught throw of a {}", tag.object_class_name())
+        }
+    }
+
+    fn symbol(name: &str) -> VALUE {
+        unsafe { crate::rb_id2sym(crate::rb_intern2(name.as_ptr() as _, name.len() as _)) }
+    }
+
     /// Wrapper over `rb_protect`, catches any Ruby exception within the given function.
     ///
     /// If you're calling into Ruby through this helper module only then you don't need this
     /// because all safe APIs run this for you. However if you're invoking raw Ruby APIs that
     /// can unwind directly, you probably do need this.
+    ///
+    /// Non-exception escapes like `throw` are converted into exceptions, see
+    /// `Unwind::into_exception`. Use `protect` to tell them apart.
+    ///
+    /// A panic inside `f` crosses Ruby's frames as a `RustPanic` exception and
+    /// resumes once it gets back here.
     pub fn catch_unwind<F>(
-        mut f: F,
+        f: F,
     ) -> Result<Value, CaughtException>
         where F: FnOnce() -> Value {
-        let mut state: libc::c_int = 1;
-
-        // WARNING: Don't read this after `rb_protect` is called. The `catch_unwind_internal`
-        // function zeros this pointer within this callframe in place upon execution.
-        // FnOnce's a bitch.
-        let mut fn_ptr_buf: *mut F = &mut f;
-
-        let fn_ptr_buf_ref: &mut *mut F = &mut fn_ptr_buf;
-        let fn_ptr_buf_ptr: *mut *mut F = fn_ptr_buf_ref as _;
-        let fn_ptr_buf_ptr_uint = fn_ptr_buf_ptr as usize; // sorry
+        protect(f).map_err(Unwind::into_exception)
+    }
 
-        let fn_ptr_buf_ptr_as_ruby_string = to_ruby::string(&fn_ptr_buf_ptr_uint.to_string());
+    /// Wrapper over `rb_protect`, catches any global escape within the given function.
+    pub fn protect<F>(
+        f: F,
+    ) -> Result<Value, Unwind>
+        where F: FnOnce() -> Value {
+        super::debug_check();
 
-        let catch_unwind_internal_args = (*fn_ptr_buf_ptr_as_ruby_string).0;
+        let mut state: libc::c_int = 1;
+        // Ruby hands the argument back to us untouched, so it can simply point at this slot.
+        let mut f = Some(f);
+        let f_slot = VALUE(&mut f as *mut Option<F> as _);
 
+        PROTECT_DEPTH.with(|depth| depth.set(depth.get() + 1));
         let result = unsafe {
-            crate::rb_protect(catch_unwind_internal::<F>, catch_unwind_internal_args, &mut state)
+            crate::rb_protect(catch_unwind_internal::<F>, f_slot, &mut state)
         };
+        PROTECT_DEPTH.with(|depth| depth.set(depth.get() - 1));
+
+        // Taken however the call ended, so a value thrown to a `catch` inside it doesn't linger.
+        let thrown = take_thrown();
 
         if state == 0 {
-            Ok(Value(result))
-        } else {
-            let exception_object: Value = Value(unsafe { crate::rb_errinfo() });
-            unsafe { crate::rb_set_errinfo(crate::Qnil) }; // clear the exception as per guidelines
+            return Ok(Value::from(result));
+        }
 
-            let message = unsafe { exception_object.send_unprotected("message", &[]).to_s_unprotected() };
-            let class_name = exception_object.object_class_name();
+        let errinfo = unsafe { crate::rb_errinfo() };
 
-            Err(CaughtException {
-                exception_object,
-                exception_class_name: class_name,
-                message,
-            })
+        Err(match state {
+            TAG_RAISE => unsafe {
+                crate::rb_set_errinfo(crate::Qnil); // clear the exception as per guidelines
+                resume_panic(errinfo);
+
+                Unwind::Raise(CaughtException::from_exception(Value::from(errinfo)))
+            },
+            TAG_RETURN => Unwind::Return(thrown_object(errinfo)),
+            TAG_BREAK => Unwind::Break(thrown_object(errinfo)),
+            TAG_NEXT => Unwind::Next(thrown_object(errinfo)),
+            TAG_RETRY => Unwind::Retry,
+            TAG_REDO => Unwind::Redo,
+            TAG_THROW => {
+                let tag = thrown_object(errinfo);
+                let value = thrown.filter(|&(thrown_tag, _)| thrown_tag == tag).map(|(_, value)| value);
+
+                Unwind::Throw { tag, value }
+            },
+            TAG_FATAL => Unwind::Fatal,
+            state => Unwind::Unknown(state),
+        })
+    }
+
+    /// Reads the object out of the `THROW_DATA` Ruby stores as error info during
+    /// non-exception jumps, laid out as `struct vm_throw_data { flags, reserved, throw_obj, .. }`.
+    fn thrown_object(errinfo: VALUE) -> Value {
+        if crate::SPECIAL_CONST_P(errinfo) {
+            return Value::from(crate::Qnil);
+        }
+
+        unsafe {
+            let flags = *(errinfo.0 as *const libc::size_t);
+            let imemo_type = (flags >> FL_USHIFT) & IMEMO_MASK;
+
+            if flags & (crate::value_type::T_MASK as libc::size_t) != T_IMEMO || imemo_type != IMEMO_THROW_DATA {
+                return Value::from(crate::Qnil);
+            }
+
+            Value::from(*(errinfo.0 as *const VALUE).add(2))
         }
     }
 
+    /// Makes `Unwind::Throw` report the thrown value, by replacing `Kernel#throw` with one
+    /// that records it before throwing. Ruby otherwise hands the value straight to the
+    /// matching `catch`, out of reach of `protect`.
+    ///
+    /// This redefines a core method for the whole interpreter, so it is opt-in. Throws
+    /// from C via `rb_throw_obj` still have no value.
+    pub fn record_throw_values() {
+        super::debug_check();
+
+        unsafe { crate::rb_define_global_function(b"throw\0".as_ptr() as _, throw_hook as *mut _, -1) };
+        RECORDING_THROWS.store(true, Ordering::SeqCst);
+    }
+
+    extern "C" fn throw_hook(
+        argc: libc::c_int,
+        argv: *const VALUE,
+        _receiver: VALUE,
+    ) -> VALUE {
+        unsafe {
+            if !(1..=2).contains(&argc) {
+                crate::rb_error_arity(argc, 1, 2);
+            }
+
+            let tag = *argv;
+            let value = if argc == 2 { *argv.add(1) } else { crate::Qnil };
+
+            if PROTECT_DEPTH.with(Cell::get) > 0 {
+                set_thrown(crate::rb_assoc_new(tag, value));
+            }
+
+            crate::rb_throw_obj(tag, value)
+        }
+    }
+
+    fn set_thrown(thrown: VALUE) {
+        unsafe { crate::rb_ivar_set(crate::rb_thread_current(), thrown_ivar(), thrown) };
+    }
+
+    /// Takes the `[tag, value]` recorded by `throw_hook`, if any.
+    fn take_thrown() -> Option<(Value, Value)> {
+        if !RECORDING_THROWS.load(Ordering::SeqCst) {
+            return None;
+        }
+
+        unsafe {
+            let thread = crate::rb_thread_current();
+            if !crate::RTEST(crate::rb_ivar_defined(thread, thrown_ivar())) { return None }
+
+            let thrown = crate::rb_ivar_get(thread, thrown_ivar());
+            if crate::NIL_P(thrown) { return None }
+
+            crate::rb_ivar_set(thread, thrown_ivar(), crate::Qnil);
+            Some((Value::from(crate::rb_ary_entry(thrown, 0)), Value::from(crate::rb_ary_entry(thrown, 1))))
+        }
+    }
+
+    fn thrown_ivar() -> crate::ID {
+        unsafe { crate::rb_intern2(THROWN_IVAR.as_ptr() as _, THROWN_IVAR.len() as _) }
+    }
+
     extern "C" fn catch_unwind_internal<F>(
-        fn_ptr_as_ruby_string: VALUE,
+        f_slot: VALUE,
     ) -> VALUE
         where F: FnOnce() -> Value {
-        let fn_ptr_buf_ptr_as_rust_string: String = unsafe {
-            Value(fn_ptr_as_ruby_string).to_s_unprotected()
-        };
+        let f_slot = f_slot.0 as *mut Option<F>;
 
-        let fn_ptr_buf_ptr_uint: usize = match fn_ptr_buf_ptr_as_rust_string.parse() {
-            Ok(uint) => uint,
-            Err(..) => {
-                eprintln!("this should never happen, choking on our own string");
+        let result = match unsafe { (*f_slot).take() } {
+            Some(f) => panic::catch_unwind(AssertUnwindSafe(f)),
+            None => {
+                eprintln!("this should never happen, protected function called twice");
                 std::process::abort();
             },
         };
-        let fn_ptr_buf_ptr: *mut *mut F = fn_ptr_buf_ptr_uint as _;
-        let fn_ptr_buf_ref: &mut *mut F = unsafe { std::mem::transmute(fn_ptr_buf_ptr) };
 
-        let fn_ptr: *mut F = std::mem::replace(fn_ptr_buf_ref, std::ptr::null_mut());
-        let fn_ref: &mut F = unsafe { std::mem::transmute(fn_ptr) };
-        let f = std::mem::replace(fn_ref, unsafe { std::mem::MaybeUninit::zeroed().assume_init() });
+        match result {
+            Ok(value) => value.0,
+            Err(payload) => unsafe { raise_panic(payload) },
+        }
+    }
+
+    /// Gets the `RustPanic` exception class, defining it if needed.
+    ///
+    /// Rust panics inside protected calls are raised into Ruby as a `RustPanic`
+    /// carrying the panic message. If the exception makes it back out to `catch_unwind`
+    /// the original panic resumes on the Rust side.
+    pub fn rust_panic_class() -> Value {
+        Value::from(unsafe { crate::rb_define_class(b"RustPanic\0".as_ptr() as _, crate::rb_eStandardError) })
+    }
+
+    /// Raises a caught panic as a `RustPanic`. Nothing on this frame needs dropping
+    /// by the time Ruby unwinds past it.
+    ///
+    /// The payload travels with the exception, so a panic raised while Ruby handles
+    /// another `RustPanic` doesn't replace it, and a `RustPanic` that Ruby rescues and
+    /// drops frees its payload when it is collected.
+    pub(crate) unsafe fn raise_panic(payload: Box<dyn Any + Send>) -> ! {
+        let exception = {
+            let message = match payload.downcast_ref::<&str>() {
+                Some(message) => message.as_bytes(),
+                None => payload.downcast_ref::<String>().map(|message| message.as_bytes()).unwrap_or(b"Box<dyn Any>"),
+            };
+
+            crate::rb_exc_new_str(rust_panic_class().0, to_ruby::bytes(message).0)
+        };
+
+        let payload = Box::into_raw(Box::new(payload)) as *mut libc::c_void;
+        let holder = crate::rb_data_object_wrap(VALUE(0), payload, None, Some(free_panic));
+        crate::rb_ivar_set(exception, panic_ivar(), holder);
+
+        crate::rb_exc_raise(exception)
+    }
+
+    extern "C" fn free_panic(payload: *mut libc::c_void) {
+        if !payload.is_null() {
+            drop(unsafe { Box::from_raw(payload as *mut Box<dyn Any + Send>) });
+        }
+    }
+
+    /// Resumes the panic that raised the given exception, if there was one.
+    fn resume_panic(exception: VALUE) {
+        let payload = unsafe {
+            if crate::SPECIAL_CONST_P(exception) || !crate::RTEST(crate::rb_ivar_defined(exception, panic_ivar())) {
+                return;
+            }
+
+            // `struct RData { flags, klass, dmark, dfree, data }`, taking the pointer so
+            // the payload is only resumed, and freed, once.
+            let holder = crate::rb_ivar_get(exception, panic_ivar());
+            let data = (holder.0 as *mut *mut libc::c_void).add(4);
+            let payload = std::mem::replace(&mut *data, std::ptr::null_mut());
+            if payload.is_null() { return }
+
+            *Box::from_raw(payload as *mut Box<dyn Any + Send>)
+        };
+
+        panic::resume_unwind(payload);
+    }
 
-        (f)().0
+    fn panic_ivar() -> crate::ID {
+        unsafe { crate::rb_intern2(PANIC_IVAR.as_ptr() as _, PANIC_IVAR.len() as _) }
     }
 
     /// Formats the exception like `<ClassName>: <message>`
@@ -219,25 +784,38 @@ mod protect {
         }
     }
 
-    impl std::error::Error for CaughtException { }
+    impl std::error::Error for CaughtException {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            self.cause.as_deref().map(|cause| cause as _)
+        }
+    }
 }
 
 mod value {
     use super::{to_ruby, CaughtException};
     use crate::VALUE;
+    use std::marker::PhantomData;
 
     /// Wraps a plain old Ruby FFI `VALUE` with much more functionality.
+    ///
+    /// Ruby objects may only be touched on the interpreter thread, so a `Value`
+    /// is neither `Send` nor `Sync`.
     #[derive(Copy, Clone, PartialEq, Eq)]
     #[repr(transparent)]
-    pub struct Value(pub VALUE);
+    pub struct Value(pub VALUE, PhantomData<*const ()>);
 
     impl Value {
         /// The Ruby `nil` value.
-        pub const NIL: Self = Value(crate::Qnil);
+        pub const NIL: Self = Value::new(crate::Qnil);
         /// The Ruby `true` value.
-        pub const TRUE: Self = Value(crate::Qtrue);
+        pub const TRUE: Self = Value::new(crate::Qtrue);
         /// The Ruby `false` value.
-        pub const FALSE: Self = Value(crate::Qfalse);
+        pub const FALSE: Self = Value::new(crate::Qfalse);
+
+        /// Wraps a raw `VALUE`. Usable in constants, unlike `Value::from`.
+        pub const fn new(value: VALUE) -> Self {
+            Value(value, PhantomData)
+        }
 
         /// Sends a Ruby method and returns the result.
         pub fn send(
@@ -256,9 +834,45 @@ mod value {
             method_name: &str,
             arguments: &[Value],
         ) -> Value {
-            let function_symbol = to_ruby::symbol(method_name);
+            super::debug_check();
+
+            let function_symbol = crate::rb_intern2(method_name.as_ptr() as _, method_name.len() as _);
             let arguments = Value::convert_array(arguments);
-            Value(crate::rb_funcallv(self.0, *function_symbol, arguments.len() as _, arguments.as_ptr()))
+            Value::from(crate::rb_funcallv(self.0, function_symbol, arguments.len() as _, arguments.as_ptr()))
+        }
+
+        /// Sends a Ruby method, passing `keywords` as keyword arguments.
+        pub fn send_with_keywords(
+            &self,
+            method_name: &str,
+            arguments: &[Value],
+            keywords: Value,
+        ) -> Result<Value, CaughtException> {
+            crate::helpers::catch_unwind(|| unsafe {
+                self.send_with_keywords_unprotected(method_name, arguments, keywords)
+            })
+        }
+
+        /// Sends a Ruby method, passing `keywords` as keyword arguments, without rescuing Ruby unwind.
+        pub unsafe fn send_with_keywords_unprotected(
+            &self,
+            method_name: &str,
+            arguments: &[Value],
+            keywords: Value,
+        ) -> Value {
+            super::debug_check();
+
+            let function_symbol = crate::rb_intern2(method_name.as_ptr() as _, method_name.len() as _);
+            let mut arguments = Value::convert_array(arguments).to_vec();
+            arguments.push(keywords.0);
+
+            #[cfg(mri_has_funcallv_kw)]
+            let result = crate::rb_funcallv_kw(self.0, function_symbol, arguments.len() as _, arguments.as_ptr(), 1);
+            // Older Rubies take a trailing `Hash` as keywords anyway.
+            #[cfg(not(mri_has_funcallv_kw))]
+            let result = crate::rb_funcallv(self.0, function_symbol, arguments.len() as _, arguments.as_ptr());
+
+            Value::from(result)
         }
 
         /// Gets a constant by name. Equivalent to `Object#const_get(constant_name)`.
@@ -274,8 +888,10 @@ mod value {
             &self,
             constant_name: &str,
         ) -> Value {
-            let constant_symbol = to_ruby::symbol(constant_name);
-            Value(crate::rb_const_get(self.0, *constant_symbol) )
+            super::debug_check();
+
+            let constant_symbol = crate::rb_intern2(constant_name.as_ptr() as _, constant_name.len() as _);
+            Value::from(crate::rb_const_get(self.0, constant_symbol))
         }
 
         /// Sets a constant. Equivalent to `Object#const_set(constant_name, value)`.
@@ -297,8 +913,10 @@ mod value {
             constant_name: &str,
             value: Value,
         ) {
-            let constant_symbol = to_ruby::symbol(constant_name);
-            crate::rb_const_set(self.0, *constant_symbol, value.0)
+            super::debug_check();
+
+            let constant_symbol = crate::rb_intern2(constant_name.as_ptr() as _, constant_name.len() as _);
+            crate::rb_const_set(self.0, constant_symbol, value.0)
         }
 
         /// Convert a Ruby value to a Rust string.
@@ -326,6 +944,8 @@ mod value {
         /// Convert a Ruby string to a Rust string.
         /// Gets the class name for an object
         pub fn object_class_name(&self) -> String {
+            super::debug_check();
+
             unsafe {
                 let cstr_ptr = crate::rb_obj_classname(self.0);
                 std::ffi::CStr::from_ptr(cstr_ptr).to_str().unwrap().to_owned()
@@ -350,6 +970,52 @@ mod value {
         /// Checks if this value is `nil`.
         pub fn is_nil(&self) -> bool { self.0 == crate::Qnil }
 
+        /// Gets the elements of a Ruby `Array`.
+        pub(crate) fn array_elements(&self) -> Result<Vec<Value>, CaughtException> {
+            super::catch_unwind(|| unsafe {
+                crate::rb_check_type(self.0, crate::T_ARRAY as _);
+                Value::NIL
+            })?;
+
+            let length = self.send("length", &[])?.to_i64()?;
+            Ok((0..length).map(|i| Value::from(unsafe { crate::rb_ary_entry(self.0, i as _) })).collect())
+        }
+
+        /// Calls `Object#to_s`, replacing any invalid UTF-8 with `U+FFFD`. Unlike `to_s`,
+        /// this copes with strings containing NUL bytes.
+        pub(crate) fn to_s_lossy(self) -> Result<String, CaughtException> {
+            let bytes = self.send("to_s", &[])?.string_bytes()?;
+
+            Ok(String::from_utf8_lossy(&bytes).into_owned())
+        }
+
+        /// Copies the bytes out of a Ruby `String`, raising `TypeError` if it isn't one.
+        pub(crate) fn string_bytes(self) -> Result<Vec<u8>, CaughtException> {
+            super::catch_unwind(|| unsafe {
+                crate::rb_check_type(self.0, crate::T_STRING as _);
+                Value::NIL
+            })?;
+
+            let length = self.send("bytesize", &[])?.to_i64()?;
+            let mut string = self.0;
+
+            unsafe {
+                let ptr = crate::rb_string_value_ptr(&mut string);
+                Ok(std::slice::from_raw_parts(ptr as *const u8, length as usize).to_vec())
+            }
+        }
+
+        /// Converts a Ruby `Integer` to a Rust integer, raising `TypeError` or `RangeError` if it can't be.
+        pub(crate) fn to_i64(self) -> Result<i64, CaughtException> {
+            let mut integer = 0;
+
+            super::catch_unwind(|| unsafe {
+                integer = crate::rb_num2long(self.0) as i64;
+                Value::NIL
+            })?;
+
+            Ok(integer)
+        }
 
         pub fn convert_array(values: &[Value]) -> &[VALUE] {
             unsafe { std::mem::transmute(values) } // safe because of #[repr(transparent)]
@@ -390,7 +1056,7 @@ mod value {
     }
 
     impl From<VALUE> for Value {
-        fn from(v: VALUE) -> Self { Value(v) }
+        fn from(v: VALUE) -> Self { Value::new(v) }
     }
 
     impl Into<VALUE> for Value {
@@ -420,21 +1086,24 @@ pub mod std {
     pub mod modules {
         use super::super::Value;
 
-        pub fn Kernel() -> Value { Value(unsafe { crate::rb_mKernel }) }
-        pub fn Math() -> Value { Value(unsafe { crate::rb_mMath }) }
+        pub fn Kernel() -> Value { Value::from(unsafe { crate::rb_mKernel }) }
+        pub fn GC() -> Value { Value::from(unsafe { crate::rb_mGC }) }
+        pub fn Math() -> Value { Value::from(unsafe { crate::rb_mMath }) }
     }
 
     pub mod classes {
         use super::super::Value;
 
-        pub fn Object() -> Value { Value(unsafe { crate::rb_cObject}) }
-        pub fn Array() -> Value { Value(unsafe { crate::rb_cArray}) }
-        pub fn Binding() -> Value { Value(unsafe { crate::rb_cBinding}) }
-        pub fn Class() -> Value { Value(unsafe { crate::rb_cClass}) }
-        pub fn Module() -> Value { Value(unsafe { crate::rb_cModule}) }
-        pub fn NilClass() -> Value { Value(unsafe { crate::rb_cNilClass}) }
-        pub fn Integer() -> Value { Value(unsafe { crate::rb_cInteger}) }
-        pub fn Hash() -> Value { Value(unsafe { crate::rb_cHash}) }
-        pub fn Float() -> Value { Value(unsafe { crate::rb_cFloat}) }
+        pub fn Object() -> Value { Value::from(unsafe { crate::rb_cObject}) }
+        pub fn Array() -> Value { Value::from(unsafe { crate::rb_cArray}) }
+        pub fn Binding() -> Value { Value::from(unsafe { crate::rb_cBinding}) }
+        pub fn Class() -> Value { Value::from(unsafe { crate::rb_cClass}) }
+        pub fn Module() -> Value { Value::from(unsafe { crate::rb_cModule}) }
+        pub fn NilClass() -> Value { Value::from(unsafe { crate::rb_cNilClass}) }
+        pub fn Integer() -> Value { Value::from(unsafe { crate::rb_cInteger}) }
+        pub fn Hash() -> Value { Value::from(unsafe { crate::rb_cHash}) }
+        pub fn Float() -> Value { Value::from(unsafe { crate::rb_cFloat}) }
+        pub fn File() -> Value { Value::from(unsafe { crate::rb_cFile}) }
+        pub fn Encoding() -> Value { Value::from(unsafe { crate::rb_cEncoding}) }
     }
 }
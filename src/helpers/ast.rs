@@ -0,0 +1,123 @@
+//! Ruby syntax trees as Rust values, via `RubyVM::AbstractSyntaxTree`.
+//!
+//! Useful for rejecting scripts that use forbidden constructs before they are run:
+//!
+//! ```no_run
+//! # use mri_sys::helpers::ast;
+//! let tree = ast::parse("`rm -rf /`").unwrap();
+//! let mut uses_backticks = false;
+//!
+//! tree.walk(&mut |node| uses_backticks |= node.kind == "XSTR" || node.kind == "DXSTR");
+//! ```
+
+use super::{to_ruby, CaughtException, Value};
+use crate::value_type::*;
+
+/// A node of the syntax tree. Equivalent to `RubyVM::AbstractSyntaxTree::Node`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Node {
+    /// The node type, such as `"FCALL"` or `"XSTR"`.
+    pub kind: String,
+    pub children: Vec<Child>,
+    pub first_lineno: u32,
+    pub first_column: u32,
+    pub last_lineno: u32,
+    pub last_column: u32,
+}
+
+/// A child of a node. Only some children are nodes themselves.
+///
+/// Symbols and strings that are not valid UTF-8 have the invalid bytes replaced
+/// with `U+FFFD`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Child {
+    Node(Node),
+    Nil,
+    Symbol(String),
+    Integer(i64),
+    String(String),
+    Array(Vec<Child>),
+    /// Any other value, such as a `Float` literal, as given by `#inspect`.
+    Other(String),
+}
+
+/// Parses Ruby code into a syntax tree without running it.
+/// Equivalent to `RubyVM::AbstractSyntaxTree.parse`.
+pub fn parse(ruby_code: &str) -> Result<Node, CaughtException> {
+    let ast_module = super::std::classes::Object().constant("RubyVM")?.constant("AbstractSyntaxTree")?;
+    let node_class = ast_module.constant("Node")?;
+    let root = ast_module.send("parse", &[*to_ruby::string(ruby_code)])?;
+
+    node(root, node_class)
+}
+
+impl Node {
+    /// Calls `f` on this node and then on every descendant node, depth first.
+    pub fn walk<F>(&self, f: &mut F)
+        where F: FnMut(&Node) {
+        f(self);
+
+        for child in self.children.iter() {
+            child.walk(f);
+        }
+    }
+
+    /// Gets the child nodes, skipping any children that are not nodes.
+    pub fn child_nodes(&self) -> impl Iterator<Item=&Node> {
+        self.children.iter().filter_map(|child| match *child {
+            Child::Node(ref node) => Some(node),
+            _ => None,
+        })
+    }
+}
+
+impl Child {
+    fn walk<F>(&self, f: &mut F)
+        where F: FnMut(&Node) {
+        match *self {
+            Child::Node(ref node) => node.walk(f),
+            Child::Array(ref children) => for child in children.iter() { child.walk(f) },
+            _ => (),
+        }
+    }
+}
+
+fn node(node: Value, node_class: Value) -> Result<Node, CaughtException> {
+    let children = node.send("children", &[])?.array_elements()?.into_iter()
+        .map(|child| self::child(child, node_class))
+        .collect::<Result<_, _>>()?;
+
+    Ok(Node {
+        kind: node.send("type", &[])?.to_s()?,
+        children,
+        first_lineno: node.send("first_lineno", &[])?.to_i64()? as u32,
+        first_column: node.send("first_column", &[])?.to_i64()? as u32,
+        last_lineno: node.send("last_lineno", &[])?.to_i64()? as u32,
+        last_column: node.send("last_column", &[])?.to_i64()? as u32,
+    })
+}
+
+fn child(child: Value, node_class: Value) -> Result<Child, CaughtException> {
+    if child.is_nil() {
+        Ok(Child::Nil)
+    } else if crate::RTEST(unsafe { crate::rb_obj_is_kind_of(child.0, node_class.0) }) {
+        node(child, node_class).map(Child::Node)
+    } else if child.is_of_value_type(T_SYMBOL) {
+        lossy_string(child.send("to_s", &[])?).map(Child::Symbol)
+    } else if child.is_of_value_type(T_FIXNUM) {
+        child.to_i64().map(Child::Integer)
+    } else if child.is_of_value_type(T_STRING) {
+        lossy_string(child).map(Child::String)
+    } else if child.is_of_value_type(T_ARRAY) {
+        child.array_elements()?.into_iter()
+            .map(|element| self::child(element, node_class))
+            .collect::<Result<_, _>>()
+            .map(Child::Array)
+    } else {
+        lossy_string(child.inspect()?).map(Child::Other)
+    }
+}
+
+fn lossy_string(string: Value) -> Result<String, CaughtException> {
+    string.string_bytes().map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+}
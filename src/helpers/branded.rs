@@ -0,0 +1,165 @@
+//! An opt-in API whose values borrow the `Ruby` token they were created from.
+//!
+//! Every value-producing call here either takes a `&Ruby` or is a method on an existing
+//! `Value<'vm>`, so values cannot outlive the `Vm` they came from, be stored in statics,
+//! or leave the interpreter thread. Misuse becomes a compile error rather than a crash.
+//!
+//! The coverage is partial. Only the calls below have branded versions. Anything else
+//! in `helpers` returns plain values, which `Value::new` brands by hand, unchecked.
+//!
+//! A value cannot outlive its `Vm`:
+//!
+//! ```compile_fail
+//! # use mri_sys::helpers::Vm;
+//! let nil = {
+//!     let vm = Vm::init().unwrap();
+//!     vm.ruby().nil()
+//! };
+//! ```
+//!
+//! be sent to another thread:
+//!
+//! ```compile_fail
+//! # use mri_sys::helpers::Vm;
+//! let vm = Vm::init().unwrap();
+//! let nil = vm.ruby().nil();
+//! std::thread::scope(|scope| { scope.spawn(move || nil.is_nil()); });
+//! ```
+//!
+//! or be returned from the executor thread:
+//!
+//! ```compile_fail
+//! # use mri_sys::helpers::executor;
+//! let handle = executor::spawn().unwrap();
+//! let nil = handle.run(|ruby| ruby.nil());
+//! ```
+
+use super::{to_ruby, Binding, CaughtException, FromRuby, Ruby, ToRuby};
+use std::marker::PhantomData;
+
+/// A Ruby value that is only usable while its interpreter is alive.
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Value<'vm> {
+    value: super::Value,
+    _vm: PhantomData<&'vm Ruby>,
+}
+
+impl Ruby {
+    /// The Ruby `nil` value.
+    pub fn nil(&self) -> Value<'_> { Value::new(self, super::Value::NIL) }
+
+    /// Converts a Rust `bool` to Ruby `true` or `false`.
+    pub fn boolean(&self, b: bool) -> Value<'_> { Value::new(self, super::Value::from(b)) }
+
+    /// Converts any Rust value with a `ToRuby` implementation.
+    pub fn convert<T: ToRuby + ?Sized>(&self, value: &T) -> Value<'_> { Value::new(self, value.to_ruby()) }
+
+    /// Converts a Rust `&str` to a Ruby `String`.
+    pub fn string(&self, string: &str) -> Value<'_> { Value::new(self, *to_ruby::string(string)) }
+
+    /// Gets a top-level constant, such as a builtin class. Equivalent to `Object.const_get`.
+    pub fn constant(&self, constant_name: &str) -> Result<Value<'_>, CaughtException> {
+        super::std::classes::Object().constant(constant_name).map(|value| Value::new(self, value))
+    }
+
+    /// The top level binding accessible in Ruby via the `TOPLEVEL_BINDING` constant.
+    pub fn top_level_binding(&self) -> Value<'_> {
+        Value::new(self, Binding::top_level().0)
+    }
+
+    /// Evaluates Ruby code in the top level binding, rescuing all Ruby exceptions.
+    pub fn eval(&self, ruby_code: &str) -> Result<Value<'_>, CaughtException> {
+        self.top_level_binding().eval(ruby_code)
+    }
+}
+
+impl<'vm> Value<'vm> {
+    /// Brands a plain value with the lifetime of the interpreter.
+    pub fn new(_ruby: &'vm Ruby, value: super::Value) -> Self {
+        Value { value, _vm: PhantomData }
+    }
+
+    /// Gets the plain value, which is no longer checked against the interpreter's lifetime.
+    pub fn unbranded(&self) -> super::Value { self.value }
+
+    /// Sends a Ruby method and returns the result.
+    pub fn send(
+        &self,
+        method_name: &str,
+        arguments: &[Value<'vm>],
+    ) -> Result<Value<'vm>, CaughtException> {
+        self.value.send(method_name, Value::unbrand_array(arguments)).map(|value| self.brand(value))
+    }
+
+    /// Gets a constant by name. Equivalent to `Object#const_get(constant_name)`.
+    pub fn constant(
+        &self,
+        constant_name: &str,
+    ) -> Result<Value<'vm>, CaughtException> {
+        self.value.constant(constant_name).map(|value| self.brand(value))
+    }
+
+    /// Sets a constant. Equivalent to `Object#const_set(constant_name, value)`.
+    pub fn set_constant(
+        &self,
+        constant_name: &str,
+        value: Value<'vm>,
+    ) -> Result<(), CaughtException> {
+        self.value.set_constant(constant_name, value.value)
+    }
+
+    /// Evaluates Ruby code in this value, which must be a `Binding`, rescuing all Ruby
+    /// exceptions. Fails with `TypeError` for any other value.
+    pub fn eval(&self, ruby_code: &str) -> Result<Value<'vm>, CaughtException> {
+        if !crate::RTEST(unsafe { crate::rb_obj_is_kind_of(self.value.0, crate::rb_cBinding) }) {
+            return Err(super::convert::type_error(self.value, "Binding"));
+        }
+
+        super::eval(ruby_code, Binding(self.value), None).map(|value| self.brand(value))
+    }
+
+    /// Convert a Ruby value to a Rust string.
+    ///
+    /// Calls `Object#to_s` and then converts the result to a string.
+    pub fn to_s(&self) -> Result<String, CaughtException> { self.value.to_s() }
+
+    /// Converts the value to a Rust type. Shorthand for `T::from_ruby`.
+    pub fn to<T: FromRuby>(&self) -> Result<T, CaughtException> { self.value.to() }
+
+    /// Calls `Object#inspect`
+    pub fn inspect(&self) -> Result<Value<'vm>, CaughtException> {
+        self.value.inspect().map(|value| self.brand(value))
+    }
+
+    /// Gets the class name for an object
+    pub fn object_class_name(&self) -> String { self.value.object_class_name() }
+
+    /// Checks if this object is of the given value type.
+    pub fn is_of_value_type(&self, value_type: crate::value_type) -> bool {
+        self.value.is_of_value_type(value_type)
+    }
+
+    /// Checks if this value is `nil`.
+    pub fn is_nil(&self) -> bool { self.value.is_nil() }
+
+    fn brand(&self, value: super::Value) -> Value<'vm> {
+        Value { value, _vm: PhantomData }
+    }
+
+    fn unbrand_array<'a>(values: &'a [Value<'vm>]) -> &'a [super::Value] {
+        unsafe { std::mem::transmute(values) } // safe because of #[repr(transparent)]
+    }
+}
+
+impl std::fmt::Display for Value<'_> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.value, fmt)
+    }
+}
+
+impl std::fmt::Debug for Value<'_> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&self.value, fmt)
+    }
+}
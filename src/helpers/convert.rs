@@ -0,0 +1,525 @@
+//! Converting between Rust and Ruby values.
+//!
+//! ```no_run
+//! # use mri_sys::helpers::{ToRuby, Value};
+//! let pair = (1u8, "one".to_owned()).to_ruby();
+//! let back: (u8, String) = pair.to().unwrap();
+//!
+//! // Raises `RangeError` on the Rust side rather than truncating.
+//! assert!(1000i32.to_ruby().to::<u8>().is_err());
+//! ```
+
+use super::{CaughtException, Value};
+use crate::value_type::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::hash::Hash;
+
+/// Converts a Rust value to a Ruby value.
+pub trait ToRuby {
+    fn to_ruby(&self) -> Value;
+}
+
+/// Converts a Ruby value to a Rust value, failing with a `TypeError` or
+/// `RangeError` style exception if it doesn't fit.
+pub trait FromRuby: Sized {
+    fn from_ruby(value: Value) -> Result<Self, CaughtException>;
+}
+
+impl Value {
+    /// Converts the value to a Rust type. Shorthand for `T::from_ruby`.
+    pub fn to<T: FromRuby>(self) -> Result<T, CaughtException> {
+        T::from_ruby(self)
+    }
+}
+
+/// Creates an exception without raising it.
+fn error(class: crate::VALUE, message: &str) -> CaughtException {
+    let exception = super::catch_unwind(|| unsafe {
+        Value::from(crate::rb_exc_new_str(class, message.to_ruby().0))
+    }).unwrap_or_else(|e| e.exception_object);
+
+    CaughtException::from_exception(exception)
+}
+
+/// Mirrors the `TypeError` Ruby raises for implicit conversions.
+pub(super) fn type_error(value: Value, expected: &str) -> CaughtException {
+    let message = format!("no implicit conversion of {} into {}", value.object_class_name(), expected);
+    error(unsafe { crate::rb_eTypeError }, &message)
+}
+
+impl ToRuby for Value {
+    fn to_ruby(&self) -> Value { *self }
+}
+
+impl FromRuby for Value {
+    fn from_ruby(value: Value) -> Result<Self, CaughtException> { Ok(value) }
+}
+
+impl ToRuby for () {
+    fn to_ruby(&self) -> Value { Value::NIL }
+}
+
+impl FromRuby for () {
+    fn from_ruby(value: Value) -> Result<Self, CaughtException> {
+        if value.is_nil() { Ok(()) } else { Err(type_error(value, "nil")) }
+    }
+}
+
+impl ToRuby for bool {
+    fn to_ruby(&self) -> Value { Value::from(*self) }
+}
+
+impl FromRuby for bool {
+    fn from_ruby(value: Value) -> Result<Self, CaughtException> {
+        if value == Value::TRUE {
+            Ok(true)
+        } else if value == Value::FALSE {
+            Ok(false)
+        } else {
+            Err(type_error(value, "true or false"))
+        }
+    }
+}
+
+/// Implements the integer conversions. Fixnums convert directly, bignums by
+/// way of their decimal representation.
+macro_rules! integer {
+    ($($ty:ty => $to_ruby:expr),* $(,)?) => {
+        $(
+            impl ToRuby for $ty {
+                fn to_ruby(&self) -> Value {
+                    $to_ruby(*self)
+                }
+            }
+
+            impl FromRuby for $ty {
+                fn from_ruby(value: Value) -> Result<Self, CaughtException> {
+                    let out_of_range = |value: Value| error(
+                        unsafe { crate::rb_eRangeError },
+                        &format!("integer {} too big to convert to `{}'", value, stringify!($ty)),
+                    );
+
+                    if value.is_of_value_type(T_FIXNUM) {
+                        <$ty>::try_from(value.to_i64()?).map_err(|_| out_of_range(value))
+                    } else if value.is_of_value_type(T_BIGNUM) {
+                        value.to_s()?.parse().map_err(|_| out_of_range(value))
+                    } else {
+                        Err(type_error(value, "Integer"))
+                    }
+                }
+            }
+        )*
+    };
+}
+
+fn signed(integer: i64) -> Value {
+    super::debug_check();
+
+    Value::from(unsafe { crate::rb_ll2inum(integer) })
+}
+
+fn unsigned(integer: u64) -> Value {
+    super::debug_check();
+
+    Value::from(unsafe { crate::rb_ull2inum(integer) })
+}
+
+/// Goes through `Integer(string)`, as the C API has nothing wider than 64 bits.
+fn wide(integer: impl ToString) -> Value {
+    let string = integer.to_string().to_ruby();
+    super::std::modules::Kernel().send("Integer", &[string]).expect("integers always parse")
+}
+
+integer! {
+    i8 => |i| signed(i as i64),
+    i16 => |i| signed(i as i64),
+    i32 => |i| signed(i as i64),
+    i64 => signed,
+    isize => |i| signed(i as i64),
+    i128 => wide,
+    u8 => |i| unsigned(i as u64),
+    u16 => |i| unsigned(i as u64),
+    u32 => |i| unsigned(i as u64),
+    u64 => unsigned,
+    usize => |i| unsigned(i as u64),
+    u128 => wide,
+}
+
+impl ToRuby for f64 {
+    fn to_ruby(&self) -> Value {
+        super::debug_check();
+
+        Value::from(unsafe { crate::rb_float_new(*self) })
+    }
+}
+
+impl FromRuby for f64 {
+    /// Accepts `Integer`s too, like Ruby's own implicit conversion.
+    fn from_ruby(value: Value) -> Result<Self, CaughtException> {
+        if [T_FLOAT, T_FIXNUM, T_BIGNUM].iter().any(|ty| value.is_of_value_type(*ty)) {
+            let mut float = 0.0;
+
+            super::catch_unwind(|| unsafe {
+                float = crate::rb_num2dbl(value.0);
+                Value::NIL
+            })?;
+
+            Ok(float)
+        } else {
+            Err(type_error(value, "Float"))
+        }
+    }
+}
+
+impl ToRuby for f32 {
+    fn to_ruby(&self) -> Value { (*self as f64).to_ruby() }
+}
+
+impl FromRuby for f32 {
+    fn from_ruby(value: Value) -> Result<Self, CaughtException> { f64::from_ruby(value).map(|f| f as f32) }
+}
+
+impl ToRuby for str {
+    fn to_ruby(&self) -> Value {
+        super::debug_check();
+
+        Value::from(unsafe { crate::rb_utf8_str_new(self.as_ptr() as _, self.len() as _) })
+    }
+}
+
+impl ToRuby for String {
+    fn to_ruby(&self) -> Value { self.as_str().to_ruby() }
+}
+
+impl FromRuby for String {
+    fn from_ruby(value: Value) -> Result<Self, CaughtException> {
+        if !value.is_of_value_type(T_STRING) {
+            return Err(type_error(value, "String"));
+        }
+
+        String::from_utf8(value.string_bytes()?)
+            .map_err(|_| error(unsafe { crate::rb_eEncodingError }, "invalid byte sequence in UTF-8"))
+    }
+}
+
+impl ToRuby for char {
+    fn to_ruby(&self) -> Value { self.encode_utf8(&mut [0; 4]).to_ruby() }
+}
+
+impl FromRuby for char {
+    fn from_ruby(value: Value) -> Result<Self, CaughtException> {
+        let string = String::from_ruby(value)?;
+        let mut chars = string.chars();
+
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(error(unsafe { crate::rb_eArgError }, &format!("expected a single character, got {:?}", string))),
+        }
+    }
+}
+
+/// Converts to a binary `String`. A `Vec<u8>` is an `Array` of `Integer`s like any
+/// other `Vec`, so use `Box<[u8]>` to convert bytes in both directions.
+impl ToRuby for [u8] {
+    fn to_ruby(&self) -> Value { super::to_ruby::bytes(self) }
+}
+
+impl ToRuby for Box<[u8]> {
+    fn to_ruby(&self) -> Value { (**self).to_ruby() }
+}
+
+/// Takes the bytes of a `String` in any encoding.
+impl FromRuby for Box<[u8]> {
+    fn from_ruby(value: Value) -> Result<Self, CaughtException> {
+        if value.is_of_value_type(T_STRING) {
+            value.string_bytes().map(Vec::into_boxed_slice)
+        } else {
+            Err(type_error(value, "String"))
+        }
+    }
+}
+
+impl<T: ToRuby> ToRuby for Option<T> {
+    fn to_ruby(&self) -> Value {
+        self.as_ref().map(T::to_ruby).unwrap_or(Value::NIL)
+    }
+}
+
+impl<T: FromRuby> FromRuby for Option<T> {
+    fn from_ruby(value: Value) -> Result<Self, CaughtException> {
+        if value.is_nil() { Ok(None) } else { T::from_ruby(value).map(Some) }
+    }
+}
+
+impl<T: ToRuby + ?Sized> ToRuby for &T {
+    fn to_ruby(&self) -> Value { (**self).to_ruby() }
+}
+
+fn array<'a, T: ToRuby + 'a>(elements: impl ExactSizeIterator<Item=&'a T>) -> Value {
+    super::debug_check();
+
+    let array = unsafe { crate::rb_ary_new_capa(elements.len() as _) };
+
+    for element in elements {
+        unsafe { crate::rb_ary_push(array, element.to_ruby().0) };
+    }
+
+    Value::from(array)
+}
+
+fn elements(value: Value) -> Result<Vec<Value>, CaughtException> {
+    if value.is_of_value_type(T_ARRAY) { value.array_elements() } else { Err(type_error(value, "Array")) }
+}
+
+impl<T: ToRuby> ToRuby for Vec<T> {
+    fn to_ruby(&self) -> Value { array(self.iter()) }
+}
+
+impl<T: FromRuby> FromRuby for Vec<T> {
+    fn from_ruby(value: Value) -> Result<Self, CaughtException> {
+        elements(value)?.into_iter().map(T::from_ruby).collect()
+    }
+}
+
+/// Always converts to an `Array` of the elements in iteration order, as `Set` may not
+/// be loaded. Call `#to_set` on it where a `Set` is needed.
+impl<T: ToRuby> ToRuby for HashSet<T> {
+    fn to_ruby(&self) -> Value { array(self.iter()) }
+}
+
+/// Accepts a `Set` or an `Array`.
+impl<T: FromRuby + Eq + Hash> FromRuby for HashSet<T> {
+    fn from_ruby(value: Value) -> Result<Self, CaughtException> {
+        let is_set = match set_class() {
+            Some(set) => crate::RTEST(unsafe { crate::rb_obj_is_kind_of(value.0, set.0) }),
+            None => false,
+        };
+        let array = if is_set { value.send("to_a", &[])? } else { value };
+
+        elements(array)?.into_iter().map(T::from_ruby).collect()
+    }
+}
+
+/// Gets `Set` if it is defined. It isn't required here, as that needs a load path
+/// and Ruby before 3.2 doesn't load it by default.
+fn set_class() -> Option<Value> {
+    let object = super::std::classes::Object();
+
+    match object.send("const_defined?", &["Set".to_ruby()]) {
+        Ok(defined) if crate::RTEST(defined.0) => object.constant("Set").ok(),
+        _ => None,
+    }
+}
+
+fn hash<'a, K: ToRuby + 'a, V: ToRuby + 'a>(entries: impl Iterator<Item=(&'a K, &'a V)>) -> Value {
+    super::debug_check();
+
+    let hash = unsafe { crate::rb_hash_new() };
+
+    for (key, value) in entries {
+        unsafe { crate::rb_hash_aset(hash, key.to_ruby().0, value.to_ruby().0) };
+    }
+
+    Value::from(hash)
+}
+
+fn entries<K: FromRuby, V: FromRuby>(value: Value) -> Result<Vec<(K, V)>, CaughtException> {
+    if !value.is_of_value_type(T_HASH) {
+        return Err(type_error(value, "Hash"));
+    }
+
+    elements(value.send("to_a", &[])?)?.into_iter().map(<(K, V)>::from_ruby).collect()
+}
+
+impl<K: ToRuby, V: ToRuby> ToRuby for HashMap<K, V> {
+    fn to_ruby(&self) -> Value { hash(self.iter()) }
+}
+
+impl<K: FromRuby + Eq + Hash, V: FromRuby> FromRuby for HashMap<K, V> {
+    fn from_ruby(value: Value) -> Result<Self, CaughtException> {
+        entries(value).map(|entries| entries.into_iter().collect())
+    }
+}
+
+impl<K: ToRuby, V: ToRuby> ToRuby for BTreeMap<K, V> {
+    fn to_ruby(&self) -> Value { hash(self.iter()) }
+}
+
+impl<K: FromRuby + Ord, V: FromRuby> FromRuby for BTreeMap<K, V> {
+    fn from_ruby(value: Value) -> Result<Self, CaughtException> {
+        entries(value).map(|entries| entries.into_iter().collect())
+    }
+}
+
+/// Implements the conversions for tuples, which are `Array`s of a fixed length.
+macro_rules! tuple {
+    ($($length:expr => ($($name:ident),*)),* $(,)?) => {
+        $(
+            impl<$($name: ToRuby),*> ToRuby for ($($name,)*) {
+                #[allow(non_snake_case)]
+                fn to_ruby(&self) -> Value {
+                    super::debug_check();
+
+                    let ($(ref $name,)*) = *self;
+                    let array = unsafe { crate::rb_ary_new_capa($length) };
+                    $( unsafe { crate::rb_ary_push(array, $name.to_ruby().0) }; )*
+
+                    Value::from(array)
+                }
+            }
+
+            impl<$($name: FromRuby),*> FromRuby for ($($name,)*) {
+                fn from_ruby(value: Value) -> Result<Self, CaughtException> {
+                    let elements = elements(value)?;
+
+                    if elements.len() != $length {
+                        let message = format!("expected an Array of length {}, got {}", $length, elements.len());
+                        return Err(error(unsafe { crate::rb_eArgError }, &message));
+                    }
+
+                    let mut elements = elements.into_iter();
+                    Ok(($($name::from_ruby(elements.next().unwrap())?,)*))
+                }
+            }
+        )*
+    };
+}
+
+tuple! {
+    1 => (A),
+    2 => (A, B),
+    3 => (A, B, C),
+    4 => (A, B, C, D),
+    5 => (A, B, C, D, E),
+    6 => (A, B, C, D, E, F),
+}
+
+/// Support for the code generated by `#[derive(ToRuby, FromRuby)]`. Not a stable API.
+#[doc(hidden)]
+pub mod derive_support {
+    use super::{elements, error, type_error, FromRuby, ToRuby};
+    use crate::helpers::{debug_check, CaughtException, Value};
+    use crate::value_type::*;
+
+    pub fn symbol(name: &str) -> Value {
+        debug_check();
+
+        Value::from(unsafe { crate::rb_id2sym(crate::rb_intern2(name.as_ptr() as _, name.len() as _)) })
+    }
+
+    pub fn hash(entries: &[(&str, Value)]) -> Value {
+        debug_check();
+
+        let hash = unsafe { crate::rb_hash_new() };
+
+        for (key, value) in entries {
+            unsafe { crate::rb_hash_aset(hash, symbol(key).0, value.0) };
+        }
+
+        Value::from(hash)
+    }
+
+    pub fn array(elements: &[Value]) -> Value {
+        debug_check();
+
+        let array = unsafe { crate::rb_ary_new_capa(elements.len() as _) };
+
+        for element in elements {
+            unsafe { crate::rb_ary_push(array, element.0) };
+        }
+
+        Value::from(array)
+    }
+
+    /// Checks the value is a `Hash`, converting instances of a Ruby `Struct` or `Data`
+    /// class with `#to_h` when `class` is given.
+    pub fn expect_hash(value: Value, class: Option<&str>) -> Result<Value, CaughtException> {
+        let value = match class {
+            Some(class) => {
+                let class = constant(class)?;
+
+                if !crate::RTEST(unsafe { crate::rb_obj_is_kind_of(value.0, class.0) }) {
+                    return Err(type_error(value, &class.to_string()));
+                }
+
+                value.send("to_h", &[])?
+            },
+            None => value,
+        };
+
+        if value.is_of_value_type(T_HASH) { Ok(value) } else { Err(type_error(value, "Hash")) }
+    }
+
+    /// Looks up a symbol key, telling a missing key apart from a `nil` value.
+    pub fn get(hash: Value, key: &str) -> Result<Option<Value>, CaughtException> {
+        let key = symbol(key);
+
+        if crate::RTEST(hash.send("key?", &[key])?.0) {
+            Ok(Some(Value::from(unsafe { crate::rb_hash_aref(hash.0, key.0) })))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn expect_array(value: Value, length: usize, type_name: &str) -> Result<Vec<Value>, CaughtException> {
+        let elements = elements(value)?;
+
+        if elements.len() == length {
+            Ok(elements)
+        } else {
+            let message = format!("{}: expected an Array of length {}, got {}", type_name, length, elements.len());
+            Err(error(unsafe { crate::rb_eArgError }, &message))
+        }
+    }
+
+    /// Gets the name of a symbol, or `None` if the value isn't one. Names that aren't
+    /// valid UTF-8 are an `EncodingError`.
+    pub fn symbol_name(value: Value) -> Result<Option<String>, CaughtException> {
+        if value.is_of_value_type(T_SYMBOL) { String::from_ruby(value.send("to_s", &[])?).map(Some) } else { Ok(None) }
+    }
+
+    /// Gets the only key and value of a `Hash` with a single symbol key.
+    pub fn tagged(value: Value, type_name: &str) -> Result<(String, Value), CaughtException> {
+        if value.is_of_value_type(T_HASH) {
+            if let [(tag, payload)] = value.send("to_a", &[])?.to::<Vec<(Value, Value)>>()?[..] {
+                if let Some(tag) = symbol_name(tag)? {
+                    return Ok((tag, payload));
+                }
+            }
+        }
+
+        let message = format!("{}: expected a Symbol or a Hash with a single Symbol key, got {}", type_name, value.inspect()?);
+        Err(error(unsafe { crate::rb_eTypeError }, &message))
+    }
+
+    /// Instantiates a Ruby `Struct` or `Data` class with the fields in order.
+    /// Panics if the class is missing or `new` raises, as the derived `to_ruby()` can't fail.
+    pub fn new_instance(class: &str, fields: &[Value]) -> Value {
+        constant(class).and_then(|class| class.send("new", fields))
+            .unwrap_or_else(|e| panic!("failed to create a {}: {}", class, e))
+    }
+
+    fn constant(path: &str) -> Result<Value, CaughtException> {
+        path.split("::").try_fold(crate::helpers::std::classes::Object(), |namespace, name| namespace.constant(name))
+    }
+
+    /// Prefixes an error with the field it came from, keeping its class.
+    pub fn in_field(e: CaughtException, type_name: &str, field: &str) -> CaughtException {
+        let class = unsafe { crate::rb_obj_class(e.exception_object.0) };
+        error(class, &format!("{}.{}: {}", type_name, field, e.message))
+    }
+
+    pub fn missing_field(type_name: &str, field: &str) -> CaughtException {
+        error(unsafe { crate::rb_eKeyError }, &format!("{}: missing field `{}'", type_name, field))
+    }
+
+    pub fn unknown_variant(type_name: &str, variant: &str) -> CaughtException {
+        error(unsafe { crate::rb_eArgError }, &format!("{}: unknown variant `{}'", type_name, variant))
+    }
+
+    /// Converts a field, for symmetry with `FromRuby` in generated code.
+    pub fn to_ruby<T: ToRuby + ?Sized>(value: &T) -> Value {
+        value.to_ruby()
+    }
+}
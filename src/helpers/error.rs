@@ -0,0 +1,340 @@
+//! Raising Ruby exceptions from Rust.
+//!
+//! Raising unwinds with `longjmp`, which skips the destructors of any Rust frames
+//! in between. Rather than calling `rb_raise` directly, a function called by Ruby
+//! returns a `RubyError` and lets `ruby_callback` raise it once nothing on the
+//! Rust side is left to drop:
+//!
+//! ```no_run
+//! use mri_sys::VALUE;
+//! use mri_sys::helpers::{ruby_callback, std::classes, RubyError, Value};
+//!
+//! extern "C" fn read_config(_receiver: VALUE, path: VALUE) -> VALUE {
+//!     ruby_callback(|| {
+//!         let path = Value::from(path).to_s()?;
+//!         let contents = std::fs::read_to_string(&path).map_err(RubyError::from_errno)?;
+//!
+//!         if contents.is_empty() {
+//!             return Err(RubyError::new(classes::Object().constant("ArgumentError")?, "empty config"));
+//!         }
+//!
+//!         Ok(*mri_sys::helpers::to_ruby::string(&contents))
+//!     })
+//! }
+//! ```
+
+use super::{catch_unwind, to_ruby, CaughtException, Value};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+
+/// An exception to be raised into Ruby by `ruby_callback`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RubyError {
+    exception: Value,
+    cause: Option<Value>,
+}
+
+impl RubyError {
+    /// Creates an exception of the given class. Equivalent to `class.new(message)`.
+    ///
+    /// If that raises, for example because `class` is not an exception class,
+    /// the error is whatever it raised instead.
+    pub fn new(class: Value, message: &str) -> Self {
+        let exception = catch_unwind(|| unsafe {
+            Value::from(crate::rb_exc_new_str(class.0, to_ruby::string(message).0))
+        }).unwrap_or_else(|e| e.exception_object);
+
+        RubyError::from_exception(exception)
+    }
+
+    /// Wraps an existing exception object.
+    pub fn from_exception(exception: Value) -> Self {
+        RubyError { exception, cause: None }
+    }
+
+    /// Converts an IO error to the matching `Errno::*` exception, or to an `IOError`
+    /// if it didn't come from the operating system.
+    pub fn from_errno(error: io::Error) -> Self {
+        RubyError::from_exception(to_ruby::io_error(&error))
+    }
+
+    /// Sets the exception's `cause`. Otherwise it is the exception being handled
+    /// when it is raised, if any.
+    pub fn with_cause(self, cause: Value) -> Self {
+        RubyError { cause: Some(cause), ..self }
+    }
+
+    /// Creates an exception of a class defined in `namespace`, such as one defined
+    /// by an `ExceptionHierarchy`.
+    ///
+    /// Only `namespace` itself is searched, like `namespace.const_get(class_name, false)`,
+    /// so a missing class is a `NameError` rather than a same-named top level class.
+    pub fn in_namespace(namespace: Value, class_name: &str, message: &str) -> Self {
+        match namespace.send("const_get", &[*to_ruby::string(class_name), Value::FALSE]) {
+            Ok(class) => RubyError::new(class, message),
+            Err(e) => RubyError::from(e),
+        }
+    }
+
+    /// Sets an instance variable on the exception, readable from Ruby through
+    /// the attributes an `ExceptionHierarchy` defines.
+    pub fn with_attribute(self, name: &str, value: Value) -> Self {
+        let ivar_name = match c_name(&format!("@{}", name)) {
+            Ok(ivar_name) => ivar_name,
+            Err(e) => return RubyError::from(e),
+        };
+
+        match catch_unwind(|| unsafe { Value::from(crate::rb_iv_set(self.exception.0, ivar_name.as_ptr(), value.0)) }) {
+            Ok(..) => self,
+            Err(e) => RubyError::from(e),
+        }
+    }
+
+    /// The exception object that will be raised.
+    pub fn exception(&self) -> Value {
+        self.exception
+    }
+
+    /// Raises the exception.
+    ///
+    /// # Safety
+    ///
+    /// Ruby unwinds past the caller with `longjmp`, so this must be called from a
+    /// function Ruby called, with no Rust values that need dropping left on the stack.
+    pub unsafe fn raise(self) -> ! {
+        match self.cause {
+            None => crate::rb_exc_raise(self.exception.0),
+            Some(cause) => raise_with_cause(self.exception.0, cause.0),
+        }
+    }
+}
+
+/// Calls `Kernel#raise(exception, cause: cause)`. Everything here lives on the stack or
+/// in Ruby's heap, so nothing is left to drop when `raise` unwinds past this frame.
+unsafe fn raise_with_cause(exception: crate::VALUE, cause: crate::VALUE) -> ! {
+    let keywords = crate::rb_hash_new();
+    crate::rb_hash_aset(keywords, crate::rb_id2sym(crate::rb_intern2(b"cause".as_ptr() as _, 5)), cause);
+
+    let arguments = [exception, keywords];
+    let raise = crate::rb_intern2(b"raise".as_ptr() as _, 5);
+
+    #[cfg(mri_has_funcallv_kw)]
+    crate::rb_funcallv_kw(crate::rb_mKernel, raise, 2, arguments.as_ptr(), 1);
+    // Older Rubies take a trailing `Hash` as keywords anyway.
+    #[cfg(not(mri_has_funcallv_kw))]
+    crate::rb_funcallv(crate::rb_mKernel, raise, 2, arguments.as_ptr());
+
+    unreachable!("Kernel#raise returned")
+}
+
+/// Lets `?` pass exceptions from protected calls on to Ruby unchanged.
+impl From<CaughtException> for RubyError {
+    fn from(e: CaughtException) -> Self {
+        RubyError::from_exception(e.exception_object)
+    }
+}
+
+/// Converts a Rust error into the Ruby exception `ruby_callback` raises for it.
+pub trait IntoRubyError {
+    fn into_ruby_error(self) -> RubyError;
+}
+
+impl IntoRubyError for RubyError {
+    fn into_ruby_error(self) -> RubyError { self }
+}
+
+impl IntoRubyError for CaughtException {
+    fn into_ruby_error(self) -> RubyError { RubyError::from(self) }
+}
+
+/// Raised as the matching `Errno::*` exception.
+impl IntoRubyError for io::Error {
+    fn into_ruby_error(self) -> RubyError { RubyError::from_errno(self) }
+}
+
+/// Defines a tree of exception classes so Ruby code can rescue Rust errors by kind.
+///
+/// `exception_hierarchy!` builds one of these from an error enum, along with the
+/// matching `IntoRubyError` implementation.
+///
+/// ```no_run
+/// # use mri_sys::helpers::{std::classes, ExceptionHierarchy};
+/// // Defines `Store::Error < StandardError` and `Store::NotFound < Store::Error`
+/// // with a `key` reader.
+/// let store = classes::Object().constant("Store").unwrap();
+/// ExceptionHierarchy::new(store, "Error")
+///     .subclass("NotFound", &["key"])
+///     .define()
+///     .unwrap();
+/// ```
+pub struct ExceptionHierarchy<'a> {
+    namespace: Value,
+    base_name: &'a str,
+    superclass: Option<Value>,
+    subclasses: Vec<(&'a str, &'a [&'a str])>,
+}
+
+impl<'a> ExceptionHierarchy<'a> {
+    /// Starts a hierarchy rooted at the class `base_name` inside the `namespace` module or class.
+    pub fn new(namespace: Value, base_name: &'a str) -> Self {
+        ExceptionHierarchy { namespace, base_name, superclass: None, subclasses: Vec::new() }
+    }
+
+    /// Sets the superclass of the base class. Defaults to `StandardError`.
+    pub fn superclass(mut self, superclass: Value) -> Self {
+        self.superclass = Some(superclass);
+        self
+    }
+
+    /// Adds a subclass of the base class, with a reader for each attribute.
+    pub fn subclass(mut self, name: &'a str, attributes: &'a [&'a str]) -> Self {
+        self.subclasses.push((name, attributes));
+        self
+    }
+
+    /// Defines the classes, returning the base class. Classes that already exist
+    /// are reused, as long as their superclass matches.
+    pub fn define(self) -> Result<Value, CaughtException> {
+        let superclass = self.superclass.unwrap_or_else(|| Value::from(unsafe { crate::rb_eStandardError }));
+        let base = define_class_under(self.namespace, self.base_name, superclass, &[])?;
+
+        for (name, attributes) in self.subclasses {
+            define_class_under(self.namespace, name, base, attributes)?;
+        }
+
+        Ok(base)
+    }
+}
+
+/// Declares an error enum together with a Ruby exception class for each variant.
+///
+/// The enum gets a `define_exception_classes` function, which defines the classes with
+/// an `ExceptionHierarchy` inside the already existing `namespace`, and an `IntoRubyError`
+/// implementation raising the variant's class. Named fields become exception attributes
+/// converted with `ToRuby`, and the message is the enum's `Display` output, so the class
+/// names in Rust and Ruby can't drift apart.
+///
+/// ```no_run
+/// use mri_sys::exception_hierarchy;
+///
+/// exception_hierarchy! {
+///     // Defines `Store::Error < StandardError`, `Store::NotFound < Store::Error` with a
+///     // `key` reader and `Store::ReadOnly < Store::Error`.
+///     #[ruby(namespace = "Store", base = "Error")]
+///     #[derive(Debug)]
+///     pub enum StoreError {
+///         NotFound { key: String },
+///         ReadOnly,
+///     }
+/// }
+///
+/// impl std::fmt::Display for StoreError {
+///     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+///         match self {
+///             StoreError::NotFound { key } => write!(fmt, "no such key: {}", key),
+///             StoreError::ReadOnly => write!(fmt, "the store is read only"),
+///         }
+///     }
+/// }
+///
+/// StoreError::define_exception_classes().unwrap();
+/// ```
+#[macro_export]
+macro_rules! exception_hierarchy {
+    (
+        #[ruby(namespace = $namespace:literal, base = $base:literal)]
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $(
+                $(#[$variant_meta:meta])*
+                $variant:ident $({ $($field:ident : $field_ty:ty),* $(,)? })?
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $(
+                $(#[$variant_meta])*
+                $variant $({ $($field : $field_ty),* })?
+            ),*
+        }
+
+        impl $name {
+            /// Defines the Ruby exception classes for this enum, returning the base class.
+            $vis fn define_exception_classes() -> ::std::result::Result<$crate::helpers::Value, $crate::helpers::CaughtException> {
+                $crate::helpers::ExceptionHierarchy::new(Self::ruby_namespace()?, $base)
+                    $(.subclass(stringify!($variant), &[$($(stringify!($field)),*)?]))*
+                    .define()
+            }
+
+            fn ruby_namespace() -> ::std::result::Result<$crate::helpers::Value, $crate::helpers::CaughtException> {
+                $crate::helpers::std::classes::Object().constant($namespace)
+            }
+        }
+
+        impl $crate::helpers::IntoRubyError for $name {
+            fn into_ruby_error(self) -> $crate::helpers::RubyError {
+                let namespace = match Self::ruby_namespace() {
+                    Ok(namespace) => namespace,
+                    Err(e) => return $crate::helpers::RubyError::from(e),
+                };
+                let message = self.to_string();
+
+                match self {
+                    $(
+                        $name::$variant $({ $($field),* })? => {
+                            $crate::helpers::RubyError::in_namespace(namespace, stringify!($variant), &message)
+                                $($(.with_attribute(stringify!($field), $crate::helpers::ToRuby::to_ruby(&$field)))*)?
+                        },
+                    )*
+                }
+            }
+        }
+    };
+}
+
+fn define_class_under(
+    namespace: Value,
+    name: &str,
+    superclass: Value,
+    attributes: &[&str],
+) -> Result<Value, CaughtException> {
+    let name = c_name(name)?;
+    let attributes = attributes.iter().map(|attribute| c_name(attribute)).collect::<Result<Vec<_>, _>>()?;
+
+    catch_unwind(|| unsafe {
+        let class = crate::rb_define_class_under(namespace.0, name.as_ptr(), superclass.0);
+
+        for attribute in attributes.iter() {
+            crate::rb_define_attr(class, attribute.as_ptr(), 1, 0);
+        }
+
+        Value::from(class)
+    })
+}
+
+/// Converts a class, attribute or instance variable name for the C API, failing with
+/// an `ArgumentError` if it contains a NUL byte.
+fn c_name(name: &str) -> Result<::std::ffi::CString, CaughtException> {
+    ::std::ffi::CString::new(name).map_err(|_| {
+        let message = format!("{:?} contains a NUL byte", name);
+        let exception = unsafe { crate::rb_exc_new_str(crate::rb_eArgError, to_ruby::string(&message).0) };
+
+        CaughtException::from_exception(Value::from(exception))
+    })
+}
+
+/// Runs the body of a function called by Ruby, raising its error once `f` has returned.
+///
+/// Panics are raised as `RustPanic`. The result is meant to be returned straight
+/// to Ruby, so the calling function should have nothing left to drop.
+pub fn ruby_callback<F, E>(f: F) -> crate::VALUE
+    where F: FnOnce() -> Result<Value, E>, E: IntoRubyError {
+    let error = match panic::catch_unwind(AssertUnwindSafe(|| f().map_err(E::into_ruby_error))) {
+        Ok(Ok(value)) => return value.0,
+        Ok(Err(error)) => error,
+        Err(payload) => unsafe { super::protect::raise_panic(payload) },
+    };
+
+    unsafe { error.raise() }
+}
@@ -0,0 +1,148 @@
+//! Matching Ruby exceptions by their builtin class.
+
+use super::{CaughtException, Value};
+
+/// The builtin exception classes, named after the Ruby class.
+///
+/// An exception's kind is its nearest builtin ancestor, so a user-defined
+/// `class ConfigError < ArgumentError` is an `ArgumentError`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RubyErrorKind {
+    Exception,
+    StandardError,
+    SystemExit,
+    Interrupt,
+    SignalException,
+    /// `fatal`, raised for unrecoverable interpreter errors.
+    Fatal,
+    ArgumentError,
+    EOFError,
+    IndexError,
+    StopIteration,
+    KeyError,
+    RangeError,
+    IOError,
+    RuntimeError,
+    SecurityError,
+    SystemCallError,
+    ThreadError,
+    TypeError,
+    ZeroDivisionError,
+    NotImplementedError,
+    NoMemoryError,
+    NoMethodError,
+    FloatDomainError,
+    LocalJumpError,
+    SystemStackError,
+    RegexpError,
+    EncodingError,
+    /// `Encoding::CompatibilityError`.
+    CompatibilityError,
+    ScriptError,
+    NameError,
+    SyntaxError,
+    LoadError,
+    /// `Math::DomainError`.
+    MathDomainError,
+}
+
+/// Every kind. Used for the ancestor lookup.
+const ALL: &[RubyErrorKind] = &[
+    RubyErrorKind::Exception,
+    RubyErrorKind::StandardError,
+    RubyErrorKind::SystemExit,
+    RubyErrorKind::Interrupt,
+    RubyErrorKind::SignalException,
+    RubyErrorKind::Fatal,
+    RubyErrorKind::ArgumentError,
+    RubyErrorKind::EOFError,
+    RubyErrorKind::IndexError,
+    RubyErrorKind::StopIteration,
+    RubyErrorKind::KeyError,
+    RubyErrorKind::RangeError,
+    RubyErrorKind::IOError,
+    RubyErrorKind::RuntimeError,
+    RubyErrorKind::SecurityError,
+    RubyErrorKind::SystemCallError,
+    RubyErrorKind::ThreadError,
+    RubyErrorKind::TypeError,
+    RubyErrorKind::ZeroDivisionError,
+    RubyErrorKind::NotImplementedError,
+    RubyErrorKind::NoMemoryError,
+    RubyErrorKind::NoMethodError,
+    RubyErrorKind::FloatDomainError,
+    RubyErrorKind::LocalJumpError,
+    RubyErrorKind::SystemStackError,
+    RubyErrorKind::RegexpError,
+    RubyErrorKind::EncodingError,
+    RubyErrorKind::CompatibilityError,
+    RubyErrorKind::ScriptError,
+    RubyErrorKind::NameError,
+    RubyErrorKind::SyntaxError,
+    RubyErrorKind::LoadError,
+    RubyErrorKind::MathDomainError,
+];
+
+impl RubyErrorKind {
+    /// Gets the Ruby exception class.
+    pub fn class(self) -> Value {
+        Value::from(unsafe { match self {
+            RubyErrorKind::Exception => crate::rb_eException,
+            RubyErrorKind::StandardError => crate::rb_eStandardError,
+            RubyErrorKind::SystemExit => crate::rb_eSystemExit,
+            RubyErrorKind::Interrupt => crate::rb_eInterrupt,
+            RubyErrorKind::SignalException => crate::rb_eSignal,
+            RubyErrorKind::Fatal => crate::rb_eFatal,
+            RubyErrorKind::ArgumentError => crate::rb_eArgError,
+            RubyErrorKind::EOFError => crate::rb_eEOFError,
+            RubyErrorKind::IndexError => crate::rb_eIndexError,
+            RubyErrorKind::StopIteration => crate::rb_eStopIteration,
+            RubyErrorKind::KeyError => crate::rb_eKeyError,
+            RubyErrorKind::RangeError => crate::rb_eRangeError,
+            RubyErrorKind::IOError => crate::rb_eIOError,
+            RubyErrorKind::RuntimeError => crate::rb_eRuntimeError,
+            RubyErrorKind::SecurityError => crate::rb_eSecurityError,
+            RubyErrorKind::SystemCallError => crate::rb_eSystemCallError,
+            RubyErrorKind::ThreadError => crate::rb_eThreadError,
+            RubyErrorKind::TypeError => crate::rb_eTypeError,
+            RubyErrorKind::ZeroDivisionError => crate::rb_eZeroDivError,
+            RubyErrorKind::NotImplementedError => crate::rb_eNotImpError,
+            RubyErrorKind::NoMemoryError => crate::rb_eNoMemError,
+            RubyErrorKind::NoMethodError => crate::rb_eNoMethodError,
+            RubyErrorKind::FloatDomainError => crate::rb_eFloatDomainError,
+            RubyErrorKind::LocalJumpError => crate::rb_eLocalJumpError,
+            RubyErrorKind::SystemStackError => crate::rb_eSysStackError,
+            RubyErrorKind::RegexpError => crate::rb_eRegexpError,
+            RubyErrorKind::EncodingError => crate::rb_eEncodingError,
+            RubyErrorKind::CompatibilityError => crate::rb_eEncCompatError,
+            RubyErrorKind::ScriptError => crate::rb_eScriptError,
+            RubyErrorKind::NameError => crate::rb_eNameError,
+            RubyErrorKind::SyntaxError => crate::rb_eSyntaxError,
+            RubyErrorKind::LoadError => crate::rb_eLoadError,
+            RubyErrorKind::MathDomainError => crate::rb_eMathDomainError,
+        } })
+    }
+
+    /// Finds the kind of an exception object by walking up its superclasses.
+    pub fn of(exception: Value) -> RubyErrorKind {
+        let mut class = unsafe { crate::rb_obj_class(exception.0) };
+
+        while !crate::NIL_P(class) {
+            if let Some(kind) = ALL.iter().find(|kind| kind.class().0 == class) {
+                return *kind;
+            }
+
+            class = unsafe { crate::rb_class_superclass(class) };
+        }
+
+        RubyErrorKind::Exception
+    }
+}
+
+impl CaughtException {
+    /// Gets the nearest builtin class of the exception, for matching on.
+    pub fn kind(&self) -> RubyErrorKind {
+        RubyErrorKind::of(self.exception_object)
+    }
+}
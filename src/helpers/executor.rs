@@ -0,0 +1,180 @@
+//! Runs the interpreter on a dedicated thread that other threads talk to through
+//! a cloneable `RubyHandle`.
+//!
+//! Ruby may only be used from the thread that initialised it. The executor owns
+//! that thread and runs the closures sent to it one at a time.
+
+use super::{Ruby, Vm};
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{mpsc, Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::{io, thread};
+
+/// The stack size of the executor thread unless otherwise specified.
+///
+/// Ruby recurses deeply on the native stack, so this is much larger than Rust's default.
+pub const DEFAULT_STACK_SIZE: usize = 64 * 1024 * 1024;
+
+type Job = Box<dyn FnOnce(&Ruby) + Send>;
+
+/// A cloneable handle to an interpreter running on its own thread.
+///
+/// The interpreter is cleaned up once every handle has been dropped, after
+/// which no other interpreter can be started.
+#[derive(Clone)]
+pub struct RubyHandle {
+    jobs: mpsc::Sender<Job>,
+}
+
+/// The executor thread has stopped, so the closure was never run.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Stopped;
+
+/// Completes with the result of a closure sent by `RubyHandle::run_async`.
+///
+/// Does not depend on any particular async runtime.
+pub struct RunFuture<R> {
+    completion: Arc<Mutex<Completion<R>>>,
+}
+
+struct Completion<R> {
+    result: Option<Result<thread::Result<R>, Stopped>>,
+    waker: Option<Waker>,
+}
+
+/// Fills in a `Completion`, marking it as stopped if dropped before the closure ran.
+struct Completer<R> {
+    completion: Option<Arc<Mutex<Completion<R>>>>,
+}
+
+/// Boots the interpreter on a new thread with the default stack size.
+pub fn spawn() -> io::Result<RubyHandle> {
+    spawn_with_stack_size(DEFAULT_STACK_SIZE)
+}
+
+/// Boots the interpreter on a new thread with the given stack size in bytes.
+///
+/// Fails if the thread cannot be spawned or the interpreter cannot be initialised.
+pub fn spawn_with_stack_size(stack_size: usize) -> io::Result<RubyHandle> {
+    let (jobs, job_receiver) = mpsc::channel::<Job>();
+    let (init_sender, init_receiver) = mpsc::channel();
+
+    thread::Builder::new()
+        .name("ruby".to_owned())
+        .stack_size(stack_size)
+        .spawn(move || {
+            let mut stack_start = crate::Qnil;
+            unsafe { crate::ruby_init_stack(&mut stack_start) };
+
+            let vm = match Vm::init() {
+                Ok(vm) => vm,
+                Err(e) => {
+                    init_sender.send(Err(e)).ok();
+                    return;
+                },
+            };
+            init_sender.send(Ok(())).ok();
+
+            for job in job_receiver {
+                job(vm.ruby());
+            }
+        })?;
+
+    match init_receiver.recv() {
+        Ok(Ok(())) => Ok(RubyHandle { jobs }),
+        Ok(Err(e)) => Err(io::Error::other(e)),
+        Err(..) => Err(io::Error::other("the Ruby executor thread panicked during startup")),
+    }
+}
+
+impl RubyHandle {
+    /// Runs `f` on the interpreter thread and blocks until it returns.
+    ///
+    /// Panics inside `f` are resumed on the calling thread. Calling this from
+    /// inside another closure on the interpreter thread deadlocks.
+    pub fn run<F, R>(&self, f: F) -> Result<R, Stopped>
+        where F: FnOnce(&Ruby) -> R + Send + 'static, R: Send + 'static {
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        self.send(move |ruby| {
+            result_sender.send(call(f, ruby)).ok();
+        });
+
+        match result_receiver.recv() {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(panic)) => panic::resume_unwind(panic),
+            Err(..) => Err(Stopped),
+        }
+    }
+
+    /// Runs `f` on the interpreter thread, returning a future that completes with its result.
+    ///
+    /// Panics inside `f` are resumed when the future is polled.
+    pub fn run_async<F, R>(&self, f: F) -> RunFuture<R>
+        where F: FnOnce(&Ruby) -> R + Send + 'static, R: Send + 'static {
+        let completion = Arc::new(Mutex::new(Completion { result: None, waker: None }));
+        let mut completer = Completer { completion: Some(completion.clone()) };
+
+        self.send(move |ruby| completer.finish(Ok(call(f, ruby))));
+
+        RunFuture { completion }
+    }
+
+    fn send<F>(&self, job: F)
+        where F: FnOnce(&Ruby) + Send + 'static {
+        // If the executor has stopped the job is dropped here, which notifies the caller.
+        self.jobs.send(Box::new(job)).ok();
+    }
+}
+
+fn call<F, R>(f: F, ruby: &Ruby) -> thread::Result<R>
+    where F: FnOnce(&Ruby) -> R {
+    panic::catch_unwind(AssertUnwindSafe(|| f(ruby)))
+}
+
+impl<R> Future for RunFuture<R> {
+    type Output = Result<R, Stopped>;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context) -> Poll<Self::Output> {
+        let mut completion = self.completion.lock().unwrap();
+
+        match completion.result.take() {
+            Some(Ok(Ok(result))) => Poll::Ready(Ok(result)),
+            Some(Ok(Err(panic))) => panic::resume_unwind(panic),
+            Some(Err(stopped)) => Poll::Ready(Err(stopped)),
+            None => {
+                completion.waker = Some(context.waker().clone());
+                Poll::Pending
+            },
+        }
+    }
+}
+
+impl<R> Completer<R> {
+    fn finish(&mut self, result: Result<thread::Result<R>, Stopped>) {
+        if let Some(completion) = self.completion.take() {
+            let mut completion = completion.lock().unwrap();
+            completion.result = Some(result);
+
+            if let Some(waker) = completion.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<R> Drop for Completer<R> {
+    fn drop(&mut self) {
+        self.finish(Err(Stopped));
+    }
+}
+
+impl std::fmt::Display for Stopped {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "the Ruby executor thread has stopped")
+    }
+}
+
+impl std::error::Error for Stopped { }
@@ -0,0 +1,153 @@
+//! Typed access to the garbage collector via the `GC` and `ObjectSpace` modules.
+
+use super::{to_ruby, CaughtException, Value};
+use super::std::modules::GC;
+use crate::value_type::{self, *};
+use std::collections::HashMap;
+
+/// Every value type that `ObjectSpace.count_objects` may report, keyed by its Ruby symbol.
+const COUNTABLE_VALUE_TYPES: &[(value_type, &str)] = &[
+    (T_NONE, "FREE"),
+    (T_OBJECT, "T_OBJECT"),
+    (T_CLASS, "T_CLASS"),
+    (T_MODULE, "T_MODULE"),
+    (T_FLOAT, "T_FLOAT"),
+    (T_STRING, "T_STRING"),
+    (T_REGEXP, "T_REGEXP"),
+    (T_ARRAY, "T_ARRAY"),
+    (T_HASH, "T_HASH"),
+    (T_STRUCT, "T_STRUCT"),
+    (T_BIGNUM, "T_BIGNUM"),
+    (T_FILE, "T_FILE"),
+    (T_DATA, "T_DATA"),
+    (T_MATCH, "T_MATCH"),
+    (T_COMPLEX, "T_COMPLEX"),
+    (T_RATIONAL, "T_RATIONAL"),
+    (T_NIL, "T_NIL"),
+    (T_TRUE, "T_TRUE"),
+    (T_FALSE, "T_FALSE"),
+    (T_SYMBOL, "T_SYMBOL"),
+    (T_FIXNUM, "T_FIXNUM"),
+    (T_UNDEF, "T_UNDEF"),
+    (T_NODE, "T_NODE"),
+    (T_ICLASS, "T_ICLASS"),
+    (T_ZOMBIE, "T_ZOMBIE"),
+];
+
+/// A snapshot of `GC.stat`.
+///
+/// Keys that the running Ruby version does not report are left as zero.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Number of times the GC has run.
+    pub count: usize,
+    pub heap_allocated_pages: usize,
+    pub heap_available_slots: usize,
+    pub heap_live_slots: usize,
+    pub heap_free_slots: usize,
+    pub heap_marked_slots: usize,
+    pub total_allocated_pages: usize,
+    pub total_freed_pages: usize,
+    pub total_allocated_objects: usize,
+    pub total_freed_objects: usize,
+    pub malloc_increase_bytes: usize,
+    pub malloc_increase_bytes_limit: usize,
+    pub minor_gc_count: usize,
+    pub major_gc_count: usize,
+    pub old_objects: usize,
+    pub old_objects_limit: usize,
+    pub oldmalloc_increase_bytes: usize,
+    pub oldmalloc_increase_bytes_limit: usize,
+}
+
+/// Gets the current garbage collector statistics. Equivalent to `GC.stat`.
+pub fn stats() -> Result<Stats, CaughtException> {
+    let stat = GC().send("stat", &[])?;
+
+    Ok(Stats {
+        count: integer_at(stat, "count")?,
+        heap_allocated_pages: integer_at(stat, "heap_allocated_pages")?,
+        heap_available_slots: integer_at(stat, "heap_available_slots")?,
+        heap_live_slots: integer_at(stat, "heap_live_slots")?,
+        heap_free_slots: integer_at(stat, "heap_free_slots")?,
+        heap_marked_slots: integer_at(stat, "heap_marked_slots")?,
+        total_allocated_pages: integer_at(stat, "total_allocated_pages")?,
+        total_freed_pages: integer_at(stat, "total_freed_pages")?,
+        total_allocated_objects: integer_at(stat, "total_allocated_objects")?,
+        total_freed_objects: integer_at(stat, "total_freed_objects")?,
+        malloc_increase_bytes: integer_at(stat, "malloc_increase_bytes")?,
+        malloc_increase_bytes_limit: integer_at(stat, "malloc_increase_bytes_limit")?,
+        minor_gc_count: integer_at(stat, "minor_gc_count")?,
+        major_gc_count: integer_at(stat, "major_gc_count")?,
+        old_objects: integer_at(stat, "old_objects")?,
+        old_objects_limit: integer_at(stat, "old_objects_limit")?,
+        oldmalloc_increase_bytes: integer_at(stat, "oldmalloc_increase_bytes")?,
+        oldmalloc_increase_bytes_limit: integer_at(stat, "oldmalloc_increase_bytes_limit")?,
+    })
+}
+
+/// Runs the garbage collector.
+/// Equivalent to `GC.start(full_mark: full, immediate_sweep: immediate_sweep)`.
+pub fn start(full: bool, immediate_sweep: bool) -> Result<(), CaughtException> {
+    let keywords = Value::from(unsafe { crate::rb_hash_new() });
+    unsafe {
+        crate::rb_hash_aset(keywords.0, symbol("full_mark").0, Value::from(full).0);
+        crate::rb_hash_aset(keywords.0, symbol("immediate_sweep").0, Value::from(immediate_sweep).0);
+    }
+
+    GC().send_with_keywords("start", &[], keywords).map(|_| ())
+}
+
+/// Enables the garbage collector. Returns `true` if it was previously disabled.
+pub fn enable() -> bool {
+    super::debug_check();
+    unsafe { crate::RTEST(crate::rb_gc_enable()) }
+}
+
+/// Disables the garbage collector. Returns `true` if it was already disabled.
+pub fn disable() -> bool {
+    super::debug_check();
+    unsafe { crate::RTEST(crate::rb_gc_disable()) }
+}
+
+/// Defragments the heap. Equivalent to `GC.compact`.
+///
+/// Raises `NotImplementedError` on platforms without compaction support.
+pub fn compact() -> Result<(), CaughtException> {
+    GC().send("compact", &[]).map(|_| ())
+}
+
+/// Counts the live objects of each value type. Equivalent to `ObjectSpace.count_objects`.
+///
+/// Free heap slots are reported under `T_NONE`. Types the running Ruby version
+/// does not report are omitted.
+pub fn count_objects() -> Result<HashMap<value_type, usize>, CaughtException> {
+    let counts = super::std::classes::Object().constant("ObjectSpace")?.send("count_objects", &[])?;
+    let mut counts_by_type = HashMap::new();
+
+    for &(ty, key) in COUNTABLE_VALUE_TYPES {
+        let count = counts.send("[]", &[symbol(key)])?;
+
+        if !count.is_nil() {
+            counts_by_type.insert(ty, to_usize(count)?);
+        }
+    }
+
+    Ok(counts_by_type)
+}
+
+fn symbol(name: &str) -> Value {
+    Value::from(unsafe { crate::rb_id2sym(*to_ruby::symbol(name)) })
+}
+
+/// Looks up an integer in a `GC.stat`-style hash, treating missing keys as zero.
+fn integer_at(hash: Value, key: &str) -> Result<usize, CaughtException> {
+    let value = hash.send("[]", &[symbol(key)])?;
+
+    if value.is_nil() { Ok(0) } else { to_usize(value) }
+}
+
+/// Fails with `RangeError` rather than wrapping negative counts around.
+fn to_usize(value: Value) -> Result<usize, CaughtException> {
+    value.to()
+}
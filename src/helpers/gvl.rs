@@ -0,0 +1,89 @@
+//! Releasing the global VM lock (GVL) around long-running Rust work.
+
+use super::{catch_unwind, CaughtException, Value};
+use std::panic::{self, AssertUnwindSafe};
+
+/// How Ruby may interrupt a function running without the GVL, for example
+/// when its thread is killed or the process receives a signal.
+#[derive(Copy, Clone)]
+pub enum Cancel<'a> {
+    /// The function cannot be interrupted. Ruby waits for it to return.
+    Never,
+    /// Ruby's builtin unblocking function for blocking IO (`RUBY_UBF_IO`).
+    Io,
+    /// Ruby's builtin unblocking function for waiting on processes (`RUBY_UBF_PROCESS`).
+    Process,
+    /// Called on another thread when Ruby wants to interrupt the function.
+    /// It must make the function return promptly (e.g. by setting a flag it polls)
+    /// and must not panic.
+    With(&'a (dyn Fn() + Sync)),
+}
+
+struct Call<F, R> {
+    f: Option<F>,
+    result: Option<std::thread::Result<R>>,
+}
+
+/// Runs `f` with the GVL released so that other Ruby threads can run in the meantime.
+///
+/// `f` must not touch the interpreter, including through free functions such as
+/// `helpers::eval`. Requiring `Send` only stops it from capturing a `Value`; calls
+/// into Ruby are not caught at compile time.
+///
+/// Panics inside `f` resume once the GVL has been reacquired. Ruby exceptions
+/// raised by pending interrupts (e.g. `Thread#raise`) are returned as errors.
+pub fn without_gvl<F, R>(
+    f: F,
+    cancel: Cancel,
+) -> Result<R, CaughtException>
+    where F: FnOnce() -> R + Send, R: Send {
+    let mut call = Call { f: Some(f), result: None };
+    let call_ptr: *mut Call<F, R> = &mut call;
+
+    let cancel_fn_ptr: *const &(dyn Fn() + Sync) = match cancel {
+        Cancel::With(ref cancel_fn) => cancel_fn,
+        _ => std::ptr::null(),
+    };
+
+    let ubf = match cancel {
+        Cancel::Never => None,
+        Cancel::Io => Some(crate::RUBY_UBF_IO()),
+        Cancel::Process => Some(crate::RUBY_UBF_PROCESS()),
+        Cancel::With(..) => Some(call_cancel as crate::rb_unblock_function_t),
+    };
+
+    catch_unwind(|| unsafe {
+        crate::rb_thread_call_without_gvl(call_without_gvl::<F, R>, call_ptr as _, ubf, cancel_fn_ptr as _);
+
+        Value::NIL
+    })?;
+
+    match call.result.expect("function was not called without the GVL") {
+        Ok(result) => Ok(result),
+        Err(panic) => panic::resume_unwind(panic),
+    }
+}
+
+extern "C" fn call_without_gvl<F, R>(
+    call_ptr: *mut libc::c_void,
+) -> *mut libc::c_void
+    where F: FnOnce() -> R {
+    let call: &mut Call<F, R> = unsafe { &mut *(call_ptr as *mut Call<F, R>) };
+
+    if let Some(f) = call.f.take() {
+        call.result = Some(panic::catch_unwind(AssertUnwindSafe(f)));
+    }
+
+    std::ptr::null_mut()
+}
+
+extern "C" fn call_cancel(
+    cancel_fn_ptr: *mut libc::c_void,
+) {
+    let cancel_fn: &&(dyn Fn() + Sync) = unsafe { &*(cancel_fn_ptr as *const &(dyn Fn() + Sync)) };
+
+    if panic::catch_unwind(AssertUnwindSafe(cancel_fn)).is_err() {
+        eprintln!("panicked while cancelling a function running without the GVL");
+        std::process::abort();
+    }
+}
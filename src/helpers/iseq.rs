@@ -0,0 +1,89 @@
+//! Precompiling Ruby source to instruction sequences, via `RubyVM::InstructionSequence`.
+//!
+//! Parsing large amounts of Ruby at startup is slow. A build script can instead
+//! compile each script once with `compile_to_binary`, write the bytes to `OUT_DIR`,
+//! and the application embeds them with `include_bytes!` and runs them with
+//! `eval_precompiled`.
+//!
+//! Binaries only load on the exact Ruby version that produced them, so the
+//! original source is kept as a fallback.
+
+use super::{to_ruby, CaughtException, SourceLocation, ToRuby, Value};
+
+/// A compiled instruction sequence. Wraps `RubyVM::InstructionSequence`.
+#[derive(Copy, Clone, Debug)]
+pub struct InstructionSequence(pub Value);
+
+impl InstructionSequence {
+    /// Compiles Ruby code without running it.
+    /// Equivalent to `RubyVM::InstructionSequence.compile`.
+    pub fn compile(
+        ruby_code: &str,
+        location: SourceLocation,
+    ) -> Result<Self, CaughtException> {
+        let file = to_ruby::string(location.file);
+
+        class()?.send("compile", &[
+            *to_ruby::string(ruby_code),
+            *file,
+            *file,
+            to_ruby::integer(location.line as i64),
+        ]).map(InstructionSequence)
+    }
+
+    /// Loads an instruction sequence serialized by `to_binary`.
+    /// Equivalent to `RubyVM::InstructionSequence.load_from_binary`.
+    ///
+    /// Raises if the binary was produced by a different Ruby version.
+    pub fn load_from_binary(binary: &[u8]) -> Result<Self, CaughtException> {
+        class()?.send("load_from_binary", &[to_ruby::bytes(binary)]).map(InstructionSequence)
+    }
+
+    /// Serializes the instruction sequence. Equivalent to `RubyVM::InstructionSequence#to_binary`.
+    pub fn to_binary(&self) -> Result<Vec<u8>, CaughtException> {
+        self.0.send("to_binary", &[])?.string_bytes()
+    }
+
+    /// Runs the instruction sequence at the top level, like `require` does.
+    /// Equivalent to `RubyVM::InstructionSequence#eval`.
+    pub fn eval(&self) -> Result<Value, CaughtException> {
+        self.0.send("eval", &[])
+    }
+}
+
+/// Compiles Ruby code to bytes that `eval_precompiled` can load.
+pub fn compile_to_binary(
+    ruby_code: &str,
+    location: SourceLocation,
+) -> Result<Vec<u8>, CaughtException> {
+    InstructionSequence::compile(ruby_code, location)?.to_binary()
+}
+
+/// Runs precompiled code at the top level.
+///
+/// Falls back to compiling `ruby_code` if the binary cannot be loaded, for example
+/// because it was built for another Ruby version, and reports why with `Kernel#warn`.
+/// Either way the code runs in a fresh top level scope that does not share local
+/// variables with `TOPLEVEL_BINDING`.
+///
+/// There is no binding parameter, as `RubyVM::InstructionSequence#eval` cannot run in
+/// a given binding. Use `helpers::eval` for code that needs one.
+pub fn eval_precompiled(
+    binary: &[u8],
+    ruby_code: &str,
+    location: SourceLocation,
+) -> Result<Value, CaughtException> {
+    match InstructionSequence::load_from_binary(binary) {
+        Ok(iseq) => iseq.eval(),
+        Err(e) => {
+            let message = format!("{}: precompiled code failed to load, compiling the source instead: {}", location.file, e);
+            super::std::modules::Kernel().send("warn", &[message.to_ruby()])?;
+
+            InstructionSequence::compile(ruby_code, location)?.eval()
+        },
+    }
+}
+
+fn class() -> Result<Value, CaughtException> {
+    super::std::classes::Object().constant("RubyVM")?.constant("InstructionSequence")
+}
@@ -0,0 +1,67 @@
+//! The guts of the `mri-run` executable, a drop-in replacement for `ruby`.
+//!
+//! Downstream crates can ship their own launcher that preloads native modules by
+//! registering init hooks in their binary's `main` and then calling `launcher::main`:
+//!
+//! ```no_run
+//! mri_sys::helpers::launcher::register_init_hook(|_ruby| {
+//!     // define native classes here
+//! });
+//!
+//! std::process::exit(mri_sys::helpers::launcher::main());
+//! ```
+
+use super::{run_main_with, Ruby};
+use crate::build_info;
+use std::ffi::OsString;
+use std::sync::Mutex;
+
+/// The flag that prints build information instead of running Ruby.
+pub const INFO_FLAG: &str = "--mri-sys-info";
+
+type InitHook = Box<dyn FnOnce(&Ruby) + Send>;
+
+static INIT_HOOKS: Mutex<Vec<InitHook>> = Mutex::new(Vec::new());
+
+/// Registers a function to run after the interpreter starts but before any user code.
+///
+/// Hooks run in the order they were registered.
+pub fn register_init_hook<F>(hook: F)
+    where F: FnOnce(&Ruby) + Send + 'static {
+    INIT_HOOKS.lock().unwrap().push(Box::new(hook));
+}
+
+/// Runs Ruby with the process arguments, returning the exit status.
+pub fn main() -> i32 {
+    let arguments: Vec<OsString> = std::env::args_os().collect();
+
+    main_with(&arguments)
+}
+
+/// Runs Ruby with the given arguments, including the program name, returning the exit status.
+pub fn main_with(arguments: &[OsString]) -> i32 {
+    if arguments.get(1).map(|argument| argument == INFO_FLAG).unwrap_or(false) {
+        print!("{}", info());
+        return 0;
+    }
+
+    run_main_with(arguments, |ruby| {
+        let hooks = std::mem::take(&mut *INIT_HOOKS.lock().unwrap());
+
+        for hook in hooks {
+            hook(ruby);
+        }
+    })
+}
+
+/// Describes what was detected about Ruby when this crate was built.
+pub fn info() -> String {
+    format!(
+        "mri-sys {}\nruby version: {}\nflonum: {}\nlink: {} {}\n",
+        env!("CARGO_PKG_VERSION"),
+        build_info::RUBY_VERSION.unwrap_or("unknown"),
+        if build_info::USE_FLONUM { "enabled" } else { "disabled" },
+        build_info::LINK_KIND,
+        build_info::RUBY_LIB,
+    )
+}
@@ -0,0 +1,483 @@
+//! Converting any `serde` model to and from Ruby values.
+//!
+//! Values are laid out like `#[derive(ToRuby, FromRuby)]` does:
+//!
+//! * Structs and maps become `Hash`es, with symbol keys for struct fields.
+//! * Sequences and tuples become `Array`s.
+//! * Unit variants become symbols and other variants a `Hash` with the variant
+//!   name as the only key, like `{ Rgb: [255, 0, 0] }`.
+//! * Bytes become binary `String`s. Numbers become `Integer`s and `Float`s, and
+//!   are range checked on the way back.
+//!
+//! ```no_run
+//! # use mri_sys::helpers::serde::{from_value, to_value};
+//! let value = to_value(&vec![(1, "one")]).unwrap();
+//! let back: Vec<(u8, String)> = from_value(value).unwrap();
+//! ```
+
+use super::derive_support::{hash, symbol};
+use super::{CaughtException, FromRuby, ToRuby, Value};
+use crate::value_type::*;
+use ::serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use ::serde::ser::{self, Serialize};
+use std::fmt;
+
+/// Converts a `serde` model to a Ruby value.
+pub fn to_value<T>(value: &T) -> Result<Value, Error>
+    where T: Serialize + ?Sized {
+    value.serialize(Serializer)
+}
+
+/// Converts a Ruby value to a `serde` model.
+pub fn from_value<T>(value: Value) -> Result<T, Error>
+    where T: DeserializeOwned {
+    T::deserialize(Deserializer(value))
+}
+
+/// Why a conversion failed.
+#[derive(Debug)]
+pub enum Error {
+    /// Reported by `serde`, for example a missing field or a number out of range.
+    Message(String),
+    /// A Ruby exception raised while reading or building a value.
+    Ruby(CaughtException),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Message(ref message) => message.fmt(fmt),
+            Error::Ruby(ref e) => e.fmt(fmt),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            Error::Message(..) => None,
+            Error::Ruby(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<CaughtException> for Error {
+    fn from(e: CaughtException) -> Self { Error::Ruby(e) }
+}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self { Error::Message(message.to_string()) }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self { Error::Message(message.to_string()) }
+}
+
+/// Builds Ruby values. Use `to_value` rather than this directly.
+pub struct Serializer;
+
+/// Collects the elements of an `Array`, optionally wrapped in a variant tag.
+///
+/// Elements go straight into the Ruby object, where the garbage collector can see them.
+pub struct SerializeArray {
+    array: Value,
+    variant: Option<&'static str>,
+}
+
+/// Collects the entries of a `Hash`, optionally wrapped in a variant tag.
+pub struct SerializeHash {
+    hash: Value,
+    key: Option<Value>,
+    variant: Option<&'static str>,
+}
+
+fn tagged(variant: Option<&'static str>, value: Value) -> Value {
+    match variant {
+        Some(variant) => hash(&[(variant, value)]),
+        None => value,
+    }
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeArray;
+    type SerializeMap = SerializeHash;
+    type SerializeStruct = SerializeHash;
+    type SerializeStructVariant = SerializeHash;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, Error> { Ok(v.to_ruby()) }
+    fn serialize_i8(self, v: i8) -> Result<Value, Error> { Ok(v.to_ruby()) }
+    fn serialize_i16(self, v: i16) -> Result<Value, Error> { Ok(v.to_ruby()) }
+    fn serialize_i32(self, v: i32) -> Result<Value, Error> { Ok(v.to_ruby()) }
+    fn serialize_i64(self, v: i64) -> Result<Value, Error> { Ok(v.to_ruby()) }
+    fn serialize_i128(self, v: i128) -> Result<Value, Error> { Ok(v.to_ruby()) }
+    fn serialize_u8(self, v: u8) -> Result<Value, Error> { Ok(v.to_ruby()) }
+    fn serialize_u16(self, v: u16) -> Result<Value, Error> { Ok(v.to_ruby()) }
+    fn serialize_u32(self, v: u32) -> Result<Value, Error> { Ok(v.to_ruby()) }
+    fn serialize_u64(self, v: u64) -> Result<Value, Error> { Ok(v.to_ruby()) }
+    fn serialize_u128(self, v: u128) -> Result<Value, Error> { Ok(v.to_ruby()) }
+    fn serialize_f32(self, v: f32) -> Result<Value, Error> { Ok(v.to_ruby()) }
+    fn serialize_f64(self, v: f64) -> Result<Value, Error> { Ok(v.to_ruby()) }
+    fn serialize_char(self, v: char) -> Result<Value, Error> { Ok(v.to_ruby()) }
+    fn serialize_str(self, v: &str) -> Result<Value, Error> { Ok(v.to_ruby()) }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, Error> { Ok(v.to_ruby()) }
+    fn serialize_none(self) -> Result<Value, Error> { Ok(Value::NIL) }
+    fn serialize_unit(self) -> Result<Value, Error> { Ok(Value::NIL) }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Error> { Ok(Value::NIL) }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Value, Error>
+        where T: Serialize + ?Sized {
+        value.serialize(self)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value, Error> {
+        Ok(symbol(variant))
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Value, Error>
+        where T: Serialize + ?Sized {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, Error>
+        where T: Serialize + ?Sized {
+        Ok(tagged(Some(variant), value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, length: Option<usize>) -> Result<SerializeArray, Error> {
+        Ok(SerializeArray::new(length.unwrap_or(0), None))
+    }
+
+    fn serialize_tuple(self, length: usize) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(length))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, length: usize) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(length))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        length: usize,
+    ) -> Result<SerializeArray, Error> {
+        Ok(SerializeArray::new(length, Some(variant)))
+    }
+
+    fn serialize_map(self, _length: Option<usize>) -> Result<SerializeHash, Error> {
+        super::debug_check();
+
+        Ok(SerializeHash { hash: Value::from(unsafe { crate::rb_hash_new() }), key: None, variant: None })
+    }
+
+    fn serialize_struct(self, _name: &'static str, length: usize) -> Result<SerializeHash, Error> {
+        self.serialize_map(Some(length))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        length: usize,
+    ) -> Result<SerializeHash, Error> {
+        Ok(SerializeHash { variant: Some(variant), ..self.serialize_map(Some(length))? })
+    }
+}
+
+impl SerializeArray {
+    fn new(capacity: usize, variant: Option<&'static str>) -> Self {
+        super::debug_check();
+
+        SerializeArray { array: Value::from(unsafe { crate::rb_ary_new_capa(capacity as _) }), variant }
+    }
+
+    fn push<T>(&mut self, value: &T) -> Result<(), Error>
+        where T: Serialize + ?Sized {
+        let value = value.serialize(Serializer)?;
+
+        unsafe { crate::rb_ary_push(self.array.0, value.0) };
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, Error> {
+        Ok(tagged(self.variant, self.array))
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+        where T: Serialize + ?Sized {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> { self.finish() }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+        where T: Serialize + ?Sized {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> { self.finish() }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+        where T: Serialize + ?Sized {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> { self.finish() }
+}
+
+impl ser::SerializeTupleVariant for SerializeArray {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+        where T: Serialize + ?Sized {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> { self.finish() }
+}
+
+impl SerializeHash {
+    fn insert(&mut self, key: Value, value: Value) {
+        unsafe { crate::rb_hash_aset(self.hash.0, key.0, value.0) };
+    }
+
+    fn finish(self) -> Result<Value, Error> {
+        Ok(tagged(self.variant, self.hash))
+    }
+}
+
+impl ser::SerializeMap for SerializeHash {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+        where T: Serialize + ?Sized {
+        self.key = Some(key.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+        where T: Serialize + ?Sized {
+        let key = self.key.take().expect("serialize_value called before serialize_key");
+        let value = value.serialize(Serializer)?;
+
+        self.insert(key, value);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> { self.finish() }
+}
+
+impl ser::SerializeStruct for SerializeHash {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+        where T: Serialize + ?Sized {
+        let value = value.serialize(Serializer)?;
+
+        self.insert(symbol(key), value);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> { self.finish() }
+}
+
+impl ser::SerializeStructVariant for SerializeHash {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+        where T: Serialize + ?Sized {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<Value, Error> { self.finish() }
+}
+
+/// Reads Ruby values. Use `from_value` rather than this directly.
+pub struct Deserializer(pub Value);
+
+impl Deserializer {
+    /// The entries of a `Hash`, in order.
+    fn entries(&self) -> Result<Vec<(Value, Value)>, Error> {
+        Ok(self.0.send("to_a", &[])?.to()?)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de> {
+        let value = self.0;
+
+        if value.is_nil() {
+            visitor.visit_unit()
+        } else if value == Value::TRUE || value == Value::FALSE {
+            visitor.visit_bool(value.to()?)
+        } else if value.is_of_value_type(T_FIXNUM) {
+            visitor.visit_i64(value.to()?)
+        } else if value.is_of_value_type(T_BIGNUM) {
+            match (value.to::<u64>(), value.to::<i128>()) {
+                (Ok(v), _) => visitor.visit_u64(v),
+                (_, Ok(v)) => visitor.visit_i128(v),
+                _ => visitor.visit_u128(value.to()?),
+            }
+        } else if value.is_of_value_type(T_FLOAT) {
+            visitor.visit_f64(value.to()?)
+        } else if value.is_of_value_type(T_SYMBOL) {
+            visitor.visit_string(String::from_ruby(value.send("to_s", &[])?)?)
+        } else if value.is_of_value_type(T_STRING) {
+            let bytes = value.string_bytes()?;
+
+            match String::from_utf8(bytes) {
+                Ok(string) => visitor.visit_string(string),
+                Err(e) => visitor.visit_byte_buf(e.into_bytes()),
+            }
+        } else if value.is_of_value_type(T_ARRAY) {
+            visitor.visit_seq(de::value::SeqDeserializer::new(value.array_elements()?.into_iter().map(Deserializer)))
+        } else if value.is_of_value_type(T_HASH) {
+            let entries = self.entries()?.into_iter().map(|(key, value)| (Deserializer(key), Deserializer(value)));
+            visitor.visit_map(de::value::MapDeserializer::new(entries))
+        } else {
+            Err(Error::Message(format!("can't deserialize a {}", value.object_class_name())))
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de> {
+        if self.0.is_nil() { visitor.visit_none() } else { visitor.visit_some(self) }
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de> {
+        if self.0.is_of_value_type(T_STRING) {
+            visitor.visit_byte_buf(self.0.string_bytes()?)
+        } else {
+            self.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+        where V: Visitor<'de> {
+        let value = self.0;
+
+        if value.is_of_value_type(T_SYMBOL) {
+            return visitor.visit_enum(String::from_ruby(value.send("to_s", &[])?)?.into_deserializer());
+        }
+
+        if value.is_of_value_type(T_STRING) {
+            return visitor.visit_enum(String::from_ruby(value)?.into_deserializer());
+        }
+
+        if value.is_of_value_type(T_HASH) {
+            if let [(variant, payload)] = self.entries()?[..] {
+                return visitor.visit_enum(Enum { variant, payload });
+            }
+        }
+
+        let message = format!("{}: expected a Symbol or a Hash with a single key, got {}", name, value.inspect()?);
+        Err(Error::Message(message))
+    }
+
+    ::serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Deserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self { self }
+}
+
+/// A variant given as a single-key `Hash`.
+struct Enum {
+    variant: Value,
+    payload: Value,
+}
+
+impl<'de> de::EnumAccess<'de> for Enum {
+    type Error = Error;
+    type Variant = Deserializer;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Deserializer), Error>
+        where V: de::DeserializeSeed<'de> {
+        Ok((seed.deserialize(Deserializer(self.variant))?, Deserializer(self.payload)))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Deserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        <() as FromRuby>::from_ruby(self.0)?;
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Error>
+        where T: de::DeserializeSeed<'de> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, _length: usize, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
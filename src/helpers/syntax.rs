@@ -0,0 +1,101 @@
+//! Checking Ruby code for syntax errors without running it.
+
+use super::{to_ruby, CaughtException, RubyErrorKind, Value};
+
+/// A single syntax error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyntaxDiagnostic {
+    /// The line of the error, starting at 1.
+    pub line: u32,
+    /// The byte offset of the error within its line, starting at 0, if Ruby reported one.
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+/// Checks Ruby code for syntax errors without running it, like `ruby -c`.
+///
+/// Uses the `Prism` parser API when the running Ruby has it, otherwise compiles
+/// the code and picks the diagnostics out of the raised `SyntaxError`. Errors
+/// whose position cannot be determined are reported at line 1.
+///
+/// If `prism` is installed but not loaded yet, the first call `require`s it, so
+/// `Prism` is defined in the interpreter from then on.
+pub fn check_syntax(ruby_code: &str, file: &str) -> Result<(), Vec<SyntaxDiagnostic>> {
+    let diagnostics = match prism() {
+        Some(prism) => check_with_prism(prism, ruby_code),
+        None => check_by_compiling(ruby_code, file),
+    }.unwrap_or_else(|e| vec![SyntaxDiagnostic { line: 1, column: None, message: e.to_string() }]);
+
+    if diagnostics.is_empty() { Ok(()) } else { Err(diagnostics) }
+}
+
+/// Gets the `Prism` module, loading it if possible.
+fn prism() -> Option<Value> {
+    let object = super::std::classes::Object();
+    let defined = object.send("const_defined?", &[*to_ruby::string("Prism")]).ok()?;
+
+    if !crate::RTEST(defined.0) {
+        super::std::modules::Kernel().send("require", &[*to_ruby::string("prism")]).ok()?;
+    }
+
+    object.constant("Prism").ok()
+}
+
+fn check_with_prism(prism: Value, ruby_code: &str) -> Result<Vec<SyntaxDiagnostic>, CaughtException> {
+    let errors = prism.send("parse", &[*to_ruby::string(ruby_code)])?.send("errors", &[])?;
+
+    errors.array_elements()?.into_iter().map(|error| {
+        let location = error.send("location", &[])?;
+
+        Ok(SyntaxDiagnostic {
+            line: location.send("start_line", &[])?.to()?,
+            column: Some(location.send("start_column", &[])?.to()?),
+            message: error.send("message", &[])?.to()?,
+        })
+    }).collect()
+}
+
+fn check_by_compiling(ruby_code: &str, file: &str) -> Result<Vec<SyntaxDiagnostic>, CaughtException> {
+    let location = super::SourceLocation::new(file, 1);
+
+    match super::iseq::InstructionSequence::compile(ruby_code, location) {
+        Ok(..) => Ok(Vec::new()),
+        Err(ref e) if e.kind() == RubyErrorKind::SyntaxError => Ok(parse_syntax_error(&e.message, file)),
+        Err(e) => Err(e),
+    }
+}
+
+/// Parses a `SyntaxError` message like
+///
+/// ```text
+/// rules.rb:2: syntax error, unexpected `end'
+///   end
+///   ^~~
+/// ```
+fn parse_syntax_error(message: &str, file: &str) -> Vec<SyntaxDiagnostic> {
+    let prefix = format!("{}:", file);
+    let mut diagnostics: Vec<SyntaxDiagnostic> = Vec::new();
+
+    for message_line in message.lines() {
+        let located = message_line.strip_prefix(&prefix).and_then(|rest| {
+            let (line, message) = rest.split_once(": ")?;
+            Some((line.parse().ok()?, message))
+        });
+
+        if let Some((line, message)) = located {
+            diagnostics.push(SyntaxDiagnostic { line, column: None, message: message.to_owned() });
+        } else if let Some(diagnostic) = diagnostics.last_mut() {
+            let marker = message_line.trim_start();
+
+            if marker.starts_with('^') && marker.chars().all(|c| c == '^' || c == '~') {
+                diagnostic.column = Some((message_line.len() - marker.len()) as u32);
+            }
+        }
+    }
+
+    if diagnostics.is_empty() {
+        diagnostics.push(SyntaxDiagnostic { line: 1, column: None, message: message.to_owned() });
+    }
+
+    diagnostics
+}
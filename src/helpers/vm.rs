@@ -0,0 +1,366 @@
+//! Owning the lifecycle of the interpreter.
+
+use super::{catch_unwind, to_ruby, Ruby, Value};
+use std::ffi::{CString, OsStr, OsString};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const UNINITIALIZED: usize = 0;
+const RUNNING: usize = 1;
+const CLEANED_UP: usize = 2;
+
+static STATE: AtomicUsize = AtomicUsize::new(UNINITIALIZED);
+
+#[cfg(feature = "debug-checks")]
+static INIT_THREAD: std::sync::OnceLock<std::thread::ThreadId> = std::sync::OnceLock::new();
+
+/// Owns the interpreter, cleaning it up when dropped.
+///
+/// Ruby cannot be restarted after cleanup, so at most one `Vm` ever exists per
+/// process. It is not `Send`, so it stays on the thread that initialised Ruby.
+pub struct Vm {
+    ruby: Ruby,
+}
+
+/// Configures and starts an interpreter that, unlike a bare `Vm::init`, can
+/// `require` the standard library.
+///
+/// Mirrors what the `ruby` executable does before running a script.
+#[derive(Clone, Debug, Default)]
+pub struct VmBuilder {
+    script_name: Option<String>,
+    arguments: Vec<String>,
+    load_path: Vec<PathBuf>,
+    verbosity: Option<Verbosity>,
+    default_external_encoding: Option<String>,
+    default_internal_encoding: Option<String>,
+}
+
+/// The warning level, equivalent to the `-W` flag.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Verbosity {
+    /// `-W0`, sets `$VERBOSE` to `nil`.
+    Silent,
+    /// `-W1`, sets `$VERBOSE` to `false`.
+    Medium,
+    /// `-W2`, sets `$VERBOSE` to `true`.
+    Verbose,
+}
+
+/// Why the interpreter could not be initialised.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InitError {
+    /// The interpreter is already running.
+    AlreadyInitialized,
+    /// The interpreter has already been cleaned up and cannot be started again.
+    CleanedUp,
+    /// `ruby_setup` failed with the given state.
+    SetupFailed(libc::c_int),
+    /// The interpreter started but raised an exception while being configured.
+    ConfigurationFailed(String),
+    /// The script name, an argument or a load path contained a NUL byte.
+    /// The interpreter was not started, so it can still be initialised.
+    InvalidArgument(String),
+}
+
+impl Vm {
+    /// Initialises a bare interpreter on the current thread.
+    ///
+    /// Use `VmBuilder` to be able to load the standard library.
+    pub fn init() -> Result<Self, InitError> {
+        claim()?;
+        setup()
+    }
+
+    /// Gets the token proving the interpreter is running on this thread.
+    pub fn ruby(&self) -> &Ruby { &self.ruby }
+
+    /// Cleans up the interpreter, running `at_exit` handlers, and returns the exit status.
+    pub fn shutdown(self) -> i32 {
+        let status = unsafe { cleanup() };
+        std::mem::forget(self);
+
+        status
+    }
+}
+
+impl Drop for Vm {
+    fn drop(&mut self) {
+        unsafe { cleanup() };
+    }
+}
+
+/// Panics unless the interpreter was started by `Vm::init`, has not been cleaned up,
+/// and the current thread holds the GVL. That is either the thread that initialised
+/// Ruby outside of `without_gvl`, or a Ruby-created thread.
+///
+/// Does nothing unless the `debug-checks` feature is enabled.
+#[inline]
+pub(crate) fn debug_check() {
+    #[cfg(feature = "debug-checks")]
+    check();
+}
+
+#[cfg(feature = "debug-checks")]
+fn check() {
+    match STATE.load(Ordering::SeqCst) {
+        UNINITIALIZED => panic!("mri-sys: Ruby used before the interpreter was initialised with `Vm::init`"),
+        CLEANED_UP => panic!("mri-sys: Ruby used after the interpreter was cleaned up"),
+        _ => (),
+    }
+
+    if unsafe { crate::ruby_thread_has_gvl_p() } != 0 {
+        return;
+    }
+
+    let current_thread = std::thread::current();
+
+    if INIT_THREAD.get() == Some(&current_thread.id()) {
+        panic!("mri-sys: Ruby used on the interpreter thread while the GVL was released, for example inside `without_gvl`");
+    } else {
+        panic!("mri-sys: Ruby used from thread {:?} ({:?}), which neither initialised the interpreter nor holds the GVL",
+               current_thread.name().unwrap_or("<unnamed>"), current_thread.id());
+    }
+}
+
+impl VmBuilder {
+    /// Creates a builder with the same defaults as the `ruby` executable.
+    pub fn new() -> Self { VmBuilder::default() }
+
+    /// Sets the script name, available in Ruby as `$0`. Defaults to `"ruby"`.
+    pub fn script_name(mut self, script_name: impl Into<String>) -> Self {
+        self.script_name = Some(script_name.into());
+        self
+    }
+
+    /// Sets the arguments available in Ruby as `ARGV`.
+    pub fn arguments<I>(mut self, arguments: I) -> Self
+        where I: IntoIterator, I::Item: Into<String> {
+        self.arguments = arguments.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Appends a directory to `$LOAD_PATH`, equivalent to the `-I` flag.
+    pub fn load_path(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.load_path.push(directory.into());
+        self
+    }
+
+    /// Sets the warning level, equivalent to the `-W` flag.
+    pub fn verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = Some(verbosity);
+        self
+    }
+
+    /// Sets `Encoding.default_external`. Defaults to the locale encoding.
+    pub fn default_external_encoding(mut self, encoding_name: impl Into<String>) -> Self {
+        self.default_external_encoding = Some(encoding_name.into());
+        self
+    }
+
+    /// Sets `Encoding.default_internal`. Defaults to `nil`.
+    pub fn default_internal_encoding(mut self, encoding_name: impl Into<String>) -> Self {
+        self.default_internal_encoding = Some(encoding_name.into());
+        self
+    }
+
+    /// Initialises the interpreter on the current thread.
+    pub fn init(self) -> Result<Vm, InitError> {
+        let script_name = self.script_name.as_deref().unwrap_or("ruby");
+        let arguments = std::iter::once(script_name).chain(self.arguments.iter().map(String::as_str))
+            .map(|argument| cstring(argument.as_bytes()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let load_path = self.load_path.iter()
+            .map(|directory| os_to_cstring(directory.as_os_str()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        claim()?;
+
+        let argv = sysinit(arguments.into_iter());
+        let vm = setup()?;
+
+        unsafe {
+            crate::ruby_init_loadpath();
+
+            for directory in load_path.iter() {
+                crate::ruby_incpush(directory.as_ptr());
+            }
+
+            crate::ruby_script(argv[0]);
+            crate::ruby_set_argv(argv.len() as libc::c_int - 1, argv[1..].as_mut_ptr());
+        }
+
+        self.configure().map_err(|e| InitError::ConfigurationFailed(e.to_string()))?;
+
+        Ok(vm)
+    }
+
+    fn configure(&self) -> Result<(), super::CaughtException> {
+        catch_unwind(|| unsafe {
+            // Loads the encoding and transcoder databases, like the `ruby` executable does.
+            let encdb = CString::new("encdb").unwrap();
+            let transdb = CString::new("enc/trans/transdb").unwrap();
+            crate::rb_enc_find_index(encdb.as_ptr());
+            crate::rb_require(transdb.as_ptr());
+
+            Value::NIL
+        })?;
+
+        let encoding = super::std::classes::Encoding();
+        let default_external = match self.default_external_encoding {
+            Some(ref encoding_name) => *to_ruby::string(encoding_name),
+            None => encoding.send("find", &[*to_ruby::string("locale")])?,
+        };
+        encoding.send("default_external=", &[default_external])?;
+
+        if let Some(ref encoding_name) = self.default_internal_encoding {
+            encoding.send("default_internal=", &[*to_ruby::string(encoding_name)])?;
+        }
+
+        if let Some(verbosity) = self.verbosity {
+            let verbose = match verbosity {
+                Verbosity::Silent => Value::NIL,
+                Verbosity::Medium => Value::FALSE,
+                Verbosity::Verbose => Value::TRUE,
+            };
+
+            unsafe { *crate::rb_ruby_verbose_ptr() = verbose.0 };
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs Ruby exactly like the `ruby` executable, returning the exit status.
+///
+/// `arguments` includes the program name, like `std::env::args_os`. Flags such as
+/// `-e`, `-r` and `-I` are handled by Ruby itself.
+pub fn run_main(arguments: &[OsString]) -> i32 {
+    run_main_with(arguments, |_| ())
+}
+
+/// Runs Ruby exactly like the `ruby` executable, returning the exit status.
+///
+/// `init` runs once the interpreter has started but before any flags are processed
+/// or user code is loaded, so it can define native classes and modules.
+///
+/// Returns 1 without starting Ruby if `arguments` is empty.
+pub fn run_main_with<F>(arguments: &[OsString], init: F) -> i32
+    where F: FnOnce(&Ruby) {
+    if arguments.is_empty() {
+        eprintln!("expected at least the program name");
+        return 1;
+    }
+
+    let arguments = match arguments.iter().map(|argument| os_to_cstring(argument)).collect::<Result<Vec<_>, _>>() {
+        Ok(arguments) => arguments,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        },
+    };
+
+    if let Err(e) = claim() {
+        eprintln!("{}", e);
+        return 1;
+    }
+
+    let argv = sysinit(arguments.into_iter());
+    let vm = match setup() {
+        Ok(vm) => vm,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        },
+    };
+
+    init(vm.ruby());
+
+    // `ruby_run_node` cleans up the interpreter itself.
+    std::mem::forget(vm);
+    let status = unsafe {
+        let node = crate::ruby_options(argv.len() as libc::c_int, argv.as_mut_ptr());
+        crate::ruby_run_node(node)
+    };
+    STATE.store(CLEANED_UP, Ordering::SeqCst);
+
+    status
+}
+
+/// Leaks the process arguments, which Ruby keeps pointers to for its whole
+/// lifetime, and passes them to `ruby_sysinit`.
+///
+/// The returned slice leaves out the null pointer that terminates `argv`, as C expects.
+fn sysinit<I>(arguments: I) -> &'static mut [*mut libc::c_char]
+    where I: Iterator<Item=CString> {
+    let argv: &mut [*mut libc::c_char] = Box::leak(
+        arguments.map(CString::into_raw).chain(std::iter::once(std::ptr::null_mut())).collect::<Vec<_>>().into_boxed_slice()
+    );
+    let (argv, _) = argv.split_at_mut(argv.len() - 1);
+    let mut argc = argv.len() as libc::c_int;
+    let mut argv_ptr = argv.as_mut_ptr();
+
+    unsafe { crate::ruby_sysinit(&mut argc, &mut argv_ptr) };
+
+    argv
+}
+
+fn cstring(bytes: &[u8]) -> Result<CString, InitError> {
+    CString::new(bytes).map_err(|_| InitError::InvalidArgument(String::from_utf8_lossy(bytes).into_owned()))
+}
+
+#[cfg(unix)]
+fn os_to_cstring(s: &OsStr) -> Result<CString, InitError> {
+    use std::os::unix::ffi::OsStrExt;
+
+    cstring(s.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn os_to_cstring(s: &OsStr) -> Result<CString, InitError> {
+    cstring(s.to_string_lossy().as_bytes())
+}
+
+/// Marks the interpreter as running, failing if it has ever been started before.
+fn claim() -> Result<(), InitError> {
+    match STATE.compare_exchange(UNINITIALIZED, RUNNING, Ordering::SeqCst, Ordering::SeqCst) {
+        Ok(..) => Ok(()),
+        Err(RUNNING) => Err(InitError::AlreadyInitialized),
+        Err(..) => Err(InitError::CleanedUp),
+    }
+}
+
+fn setup() -> Result<Vm, InitError> {
+    #[cfg(feature = "debug-checks")]
+    INIT_THREAD.set(std::thread::current().id()).ok();
+
+    let state = unsafe { crate::ruby_setup() };
+
+    if state == 0 {
+        Ok(Vm { ruby: unsafe { Ruby::assume_initialized() } })
+    } else {
+        STATE.store(CLEANED_UP, Ordering::SeqCst);
+        Err(InitError::SetupFailed(state))
+    }
+}
+
+unsafe fn cleanup() -> i32 {
+    let status = crate::ruby_cleanup(0);
+    STATE.store(CLEANED_UP, Ordering::SeqCst);
+
+    status
+}
+
+impl std::fmt::Display for InitError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            InitError::AlreadyInitialized => write!(fmt, "the Ruby interpreter is already initialised"),
+            InitError::CleanedUp => write!(fmt, "the Ruby interpreter has been cleaned up and cannot be restarted"),
+            InitError::SetupFailed(state) => write!(fmt, "failed to set up the Ruby interpreter (state {})", state),
+            InitError::ConfigurationFailed(message) => write!(fmt, "failed to configure the Ruby interpreter: {}", message),
+            InitError::InvalidArgument(argument) => write!(fmt, "{:?} contains a NUL byte", argument),
+        }
+    }
+}
+
+impl std::error::Error for InitError { }
@@ -5,6 +5,8 @@ pub use self::statics::*;
 pub use self::vt::*;
 pub use self::ty::*;
 
+pub mod build_info;
+
 mod value;
 mod vt;
 mod consts;
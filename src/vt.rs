@@ -1,7 +1,7 @@
 pub use self::value_type::*;
 
 #[repr(i32)]
-#[derive(PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[allow(non_camel_case_types)]
 pub enum value_type {
     T_NONE     = 0x00,
@@ -0,0 +1,24 @@
+#![cfg(feature = "helpers")]
+
+use mri_sys::helpers::ast::{self, Child};
+use mri_sys::helpers::Vm;
+
+#[test]
+fn parse_builds_a_node_tree() {
+    let vm = Vm::init().unwrap();
+
+    let tree = ast::parse("puts 1\n`ls`").unwrap();
+    let mut kinds = Vec::new();
+    tree.walk(&mut |node| kinds.push(node.kind.clone()));
+    assert_eq!(tree.kind, "SCOPE");
+    assert!(kinds.iter().any(|kind| kind == "XSTR"), "{:?}", kinds);
+
+    // Untrusted scripts may contain strings that aren't valid C or UTF-8 strings.
+    let tree = ast::parse(r#"[:"a\0b", "a\0b", "\xff"]"#).unwrap();
+    let mut leaves = Vec::new();
+    tree.walk(&mut |node| leaves.extend(node.children.iter().filter(|child| matches!(child, Child::String(..))).cloned()));
+    assert!(leaves.contains(&Child::String("a\0b".to_owned())), "{:?}", leaves);
+    assert!(leaves.contains(&Child::String("\u{fffd}".to_owned())), "{:?}", leaves);
+
+    assert_eq!(vm.shutdown(), 0);
+}
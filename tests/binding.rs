@@ -0,0 +1,27 @@
+#![cfg(feature = "helpers")]
+
+use mri_sys::helpers::{eval, Binding, SourceLocation, Vm};
+
+#[test]
+fn isolated_bindings_keep_their_own_locals() {
+    let vm = Vm::init().unwrap();
+    let ten = eval("10", Binding::top_level(), None).unwrap();
+
+    let isolated = Binding::isolated();
+    isolated.local_variable_set("x", ten).unwrap();
+    assert_eq!(isolated.local_variable_get("x").unwrap(), ten);
+    assert_eq!(isolated.local_variables().unwrap(), vec!["x".to_owned()]);
+    assert_eq!(eval("x * 2", isolated, None).unwrap().to_s().unwrap(), "20");
+    assert!(isolated.local_variable_get("y").is_err());
+
+    assert!(!Binding::isolated().local_variable_defined("x").unwrap());
+    assert!(!Binding::top_level().local_variable_defined("x").unwrap());
+    assert_ne!(isolated.receiver().unwrap(), Binding::isolated().receiver().unwrap());
+
+    let located = Binding(eval("binding", Binding::top_level(), Some(SourceLocation::new("located.rb", 7))).unwrap());
+    assert_eq!(located.source_location().unwrap(), ("located.rb".to_owned(), 7));
+    let short = Binding(eval("b = binding; def b.source_location; ['short.rb']; end; b", Binding::top_level(), None).unwrap());
+    assert_eq!(short.source_location().unwrap_err().exception_class_name, "ArgumentError");
+
+    assert_eq!(vm.shutdown(), 0);
+}
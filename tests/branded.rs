@@ -0,0 +1,18 @@
+#![cfg(feature = "helpers")]
+
+use mri_sys::helpers::Vm;
+
+#[test]
+fn branded_values_only_eval_in_bindings() {
+    let vm = Vm::init().unwrap();
+    let ruby = vm.ruby();
+
+    assert_eq!(ruby.eval("1 + 1").unwrap().to::<i64>(), Ok(2));
+    let binding = ruby.eval("x = 3; binding").unwrap();
+    assert_eq!(binding.eval("x * 2").unwrap().to::<i64>(), Ok(6));
+
+    let e = ruby.string("not a binding").eval("1").unwrap_err();
+    assert_eq!(e.exception_class_name, "TypeError");
+
+    assert_eq!(vm.shutdown(), 0);
+}
@@ -0,0 +1,24 @@
+//! Helpers shared by the integration tests. Each test binary starts at most one
+//! interpreter, as Ruby can't be initialised twice in a process.
+
+#![allow(dead_code)]
+
+use mri_sys::helpers::{eval, Binding, CaughtException, Value, Vm};
+
+/// Evaluates Ruby code at the top level.
+pub fn run(code: &str) -> Result<Value, CaughtException> {
+    eval(code, Binding::top_level(), None)
+}
+
+/// Evaluates Ruby code at the top level that is expected to succeed.
+pub fn ruby(code: &str) -> Value {
+    run(code).unwrap()
+}
+
+/// Runs `f` with an interpreter, checking that it shuts down cleanly afterwards.
+pub fn with_vm<F>(f: F)
+    where F: FnOnce() {
+    let vm = Vm::init().unwrap();
+    f();
+    assert_eq!(vm.shutdown(), 0);
+}
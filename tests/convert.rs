@@ -0,0 +1,34 @@
+#![cfg(feature = "helpers")]
+
+mod common;
+
+use common::ruby;
+use mri_sys::helpers::{ToRuby, Value};
+use std::collections::{BTreeMap, HashSet};
+
+#[test]
+fn values_convert_both_ways() {
+    common::with_vm(|| {
+        assert_eq!(u64::MAX.to_ruby().to::<u64>(), Ok(u64::MAX));
+        assert_eq!(i128::MIN.to_ruby().to::<i128>(), Ok(i128::MIN));
+        assert_eq!((-1i32).to_ruby().to::<u32>().unwrap_err().exception_class_name, "RangeError");
+        assert_eq!("1".to_ruby().to::<i32>().unwrap_err().exception_class_name, "TypeError");
+        assert_eq!((Some('x'), vec![1.5f64], ()).to_ruby().to::<(Option<char>, Vec<f64>, ())>(), Ok((Some('x'), vec![1.5], ())));
+
+        let map: BTreeMap<String, bool> = ruby("{ 'a' => true }").to().unwrap();
+        assert_eq!(map.to_ruby().send("==", &[ruby("{ 'a' => true }")]), Ok(Value::TRUE));
+
+        // Byte slices are binary strings, while a `Vec<u8>` is an array like any other.
+        let bytes: Box<[u8]> = Box::new([0, 0xff]);
+        assert_eq!(bytes.to_ruby().send("encoding", &[]).unwrap().to_s().unwrap(), "ASCII-8BIT");
+        assert_eq!(bytes.to_ruby().to::<Box<[u8]>>(), Ok(bytes));
+        assert_eq!(ruby("'a'.b").to::<Box<[u8]>>(), Ok(Box::from(&b"a"[..])));
+        assert_eq!(vec![1u8].to_ruby().to::<Vec<u8>>(), Ok(vec![1]));
+
+        let set: HashSet<u8> = [1, 2].iter().copied().collect();
+        assert_eq!(set.to_ruby().object_class_name(), "Array");
+        assert_eq!(set.to_ruby().to::<HashSet<u8>>(), Ok(set.clone()));
+        assert_eq!(ruby("[2, 1, 2]").to::<HashSet<u8>>(), Ok(set.clone()));
+        assert_eq!(ruby("require 'set'; Set[1, 2]").to::<HashSet<u8>>(), Ok(set));
+    });
+}
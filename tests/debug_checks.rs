@@ -0,0 +1,9 @@
+#![cfg(feature = "debug-checks")]
+
+use mri_sys::helpers::{eval, Binding};
+
+#[test]
+#[should_panic(expected = "before the interpreter was initialised")]
+fn using_ruby_before_init_panics() {
+    eval("1 + 1", Binding::top_level(), None).ok();
+}
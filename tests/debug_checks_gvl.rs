@@ -0,0 +1,11 @@
+#![cfg(feature = "debug-checks")]
+
+use mri_sys::helpers::{eval, without_gvl, Binding, Cancel, Vm};
+
+#[test]
+#[should_panic(expected = "while the GVL was released")]
+fn using_ruby_without_the_gvl_panics() {
+    let _vm = Vm::init().unwrap();
+
+    without_gvl(|| { eval("1 + 1", Binding::top_level(), None).ok(); }, Cancel::Never).ok();
+}
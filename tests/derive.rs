@@ -0,0 +1,66 @@
+#![cfg(feature = "derive")]
+
+mod common;
+
+use common::ruby;
+use mri_sys::helpers::{FromRuby, ToRuby};
+
+#[derive(ToRuby, FromRuby, Debug, PartialEq)]
+struct Config {
+    name: String,
+    #[ruby(rename = "listen_port")]
+    port: u16,
+    #[ruby(default)]
+    tags: Vec<String>,
+    comment: Option<String>,
+    #[ruby(skip)]
+    cache: Vec<u8>,
+}
+
+#[derive(ToRuby, FromRuby, Debug, PartialEq)]
+#[ruby(class = "Point")]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[derive(ToRuby, FromRuby, Debug, PartialEq)]
+struct Pair(u8, String);
+
+#[derive(ToRuby, FromRuby, Debug, PartialEq)]
+enum Shape {
+    Empty,
+    #[ruby(rename = "circle")]
+    Circle { radius: f64 },
+    Line(Point, Point),
+}
+
+#[test]
+fn derived_conversions_round_trip() {
+    common::with_vm(|| {
+        let config: Config = ruby("{ name: 'web', listen_port: 8080 }").to().unwrap();
+        assert_eq!(config, Config { name: "web".to_owned(), port: 8080, tags: vec![], comment: None, cache: vec![] });
+        assert_eq!(config.to_ruby().send("keys", &[]).unwrap().inspect().unwrap().to_s().unwrap(), "[:name, :listen_port, :tags, :comment]");
+
+        let e = ruby("{ name: 'web', listen_port: 80_000 }").to::<Config>().unwrap_err();
+        assert_eq!(e.exception_class_name, "RangeError");
+        assert!(e.message.starts_with("Config.listen_port: "), "{}", e.message);
+        let e = ruby("{ listen_port: 80 }").to::<Config>().unwrap_err();
+        assert_eq!((e.exception_class_name.as_str(), e.message.as_str()), ("KeyError", "Config: missing field `name'"));
+
+        ruby("Point = Struct.new(:x, :y)");
+        let point = Point { x: 1, y: 2 };
+        assert_eq!(point.to_ruby().object_class_name(), "Point");
+        assert_eq!(Point::from_ruby(point.to_ruby()), Ok(point));
+
+        assert_eq!(ruby("[1, 'one']").to::<Pair>(), Ok(Pair(1, "one".to_owned())));
+
+        assert_eq!(ruby(":Empty").to::<Shape>(), Ok(Shape::Empty));
+        assert_eq!(ruby("{ circle: { radius: 1.5 } }").to::<Shape>(), Ok(Shape::Circle { radius: 1.5 }));
+        let line = Shape::Line(Point { x: 0, y: 0 }, Point { x: 1, y: 1 });
+        assert_eq!(Shape::from_ruby(line.to_ruby()), Ok(line));
+        assert_eq!(ruby(":Square").to::<Shape>().unwrap_err().message, "Shape: unknown variant `Square'");
+        assert_eq!(ruby(":\"Em\\0pty\"").to::<Shape>().unwrap_err().message, "Shape: unknown variant `Em\0pty'");
+        assert_eq!(ruby("\"\\xff\".b.to_sym").to::<Shape>().unwrap_err().exception_class_name, "EncodingError");
+    });
+}
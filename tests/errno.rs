@@ -0,0 +1,18 @@
+#![cfg(feature = "helpers")]
+
+mod common;
+
+use common::run;
+use mri_sys::helpers::RubyError;
+
+#[test]
+fn errno_exceptions_round_trip_through_io_errors() {
+    common::with_vm(|| {
+        let enoent = RubyError::from_errno(std::io::Error::from_raw_os_error(libc::ENOENT));
+        assert_eq!(enoent.exception().object_class_name(), "Errno::ENOENT");
+
+        let e = run("File.read('/nonexistent/mri-sys')").unwrap_err();
+        assert_eq!(e.as_io_error().and_then(|e| e.raw_os_error()), Some(libc::ENOENT));
+        assert_eq!(run("raise 'not io'").unwrap_err().as_io_error().map(|e| e.kind()), None);
+    });
+}
@@ -0,0 +1,16 @@
+#![cfg(feature = "helpers")]
+
+mod common;
+
+use common::run;
+use mri_sys::helpers::RubyErrorKind;
+
+#[test]
+fn exceptions_match_their_nearest_builtin_kind() {
+    common::with_vm(|| {
+        assert_eq!(run("raise ArgumentError").unwrap_err().kind(), RubyErrorKind::ArgumentError);
+        assert_eq!(run("class ConfigError < KeyError; end; raise ConfigError").unwrap_err().kind(), RubyErrorKind::KeyError);
+        assert_eq!(run("[].fetch(1)").unwrap_err().kind(), RubyErrorKind::IndexError);
+        assert_eq!(RubyErrorKind::of(RubyErrorKind::ZeroDivisionError.class().send("new", &[]).unwrap()), RubyErrorKind::ZeroDivisionError);
+    });
+}
@@ -0,0 +1,40 @@
+#![cfg(feature = "helpers")]
+
+use mri_sys::helpers::{eval, eval_file, Binding, SourceLocation, Vm};
+
+#[test]
+fn eval_reports_source_locations() {
+    let vm = Vm::init().unwrap();
+
+    let location = SourceLocation::new("template.rb", 42);
+    assert_eq!(eval("__FILE__", Binding::top_level(), Some(location)).unwrap().to_s().unwrap(), "template.rb");
+    assert_eq!(eval("\n__LINE__", Binding::top_level(), Some(location)).unwrap().to_s().unwrap(), "43");
+
+    let path = std::env::temp_dir().join(format!("mri-sys-eval-file-{}.rb", std::process::id()));
+    std::fs::write(&path, "x = 20\n[__FILE__, __LINE__, x + 1].join(':')\n").unwrap();
+    let real_path = path.canonicalize().unwrap();
+
+    let result = eval_file(&path, Binding::top_level());
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(result.unwrap().to_s().unwrap(), format!("{}:2:21", real_path.display()));
+
+    let missing = eval_file(&path, Binding::top_level()).unwrap_err();
+    assert_eq!(missing.exception_class_name, "Errno::ENOENT");
+
+    // Paths that aren't UTF-8 are passed to Ruby byte for byte.
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        let mut name = format!("mri-sys-eval-file-{}-", std::process::id()).into_bytes();
+        name.extend_from_slice(b"\xff.rb");
+        let path = std::env::temp_dir().join(std::ffi::OsStr::from_bytes(&name));
+        std::fs::write(&path, "__FILE__.bytes.include?(0xff)\n").unwrap();
+
+        let result = eval_file(&path, Binding::top_level());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(result.unwrap().to_s().unwrap(), "true");
+    }
+
+    assert_eq!(vm.shutdown(), 0);
+}
@@ -0,0 +1,32 @@
+#![cfg(feature = "helpers")]
+
+mod common;
+
+use common::{ruby, run};
+use mri_sys::helpers::{eval, Binding, Frame, SourceLocation};
+
+#[test]
+fn caught_exceptions_expose_causes_and_backtraces() {
+    common::with_vm(|| {
+        let location = SourceLocation::new("causes.rb", 1);
+        let code = "begin\n  raise 'inner'\nrescue\n  raise ArgumentError, 'outer'\nend";
+        let e = eval(code, Binding::top_level(), Some(location)).unwrap_err();
+
+        assert!(e.is_a(ruby("StandardError")));
+        assert!(!e.is_a(ruby("KeyError")));
+        assert_eq!(e.cause().map(|cause| cause.message.as_str()), Some("inner"));
+        assert!(std::error::Error::source(&e).is_some());
+        assert_eq!(e.backtrace()[0], Frame { path: "causes.rb".to_owned(), lineno: 4, label: "<main>".to_owned() });
+        assert!(e.full_message().contains("outer (ArgumentError)"), "{}", e.full_message());
+
+        // Broken `#message` and `#cause` methods don't escape into Rust.
+        ruby("class Broken < StandardError; def message; raise 'no message'; end; def cause; raise 'no cause'; end; end");
+        let e = run("raise Broken").unwrap_err();
+        assert_eq!(e.exception_class_name, "Broken");
+        assert!(e.message.contains("Broken"), "{}", e.message);
+        assert!(e.cause().is_none());
+
+        let e = run(r#"raise "nul\0byte \xff""#).unwrap_err();
+        assert_eq!(e.message, "nul\0byte \u{fffd}");
+    });
+}
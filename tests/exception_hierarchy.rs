@@ -0,0 +1,74 @@
+#![cfg(feature = "helpers")]
+
+mod common;
+
+use common::{ruby, run};
+use mri_sys::exception_hierarchy;
+use mri_sys::helpers::{ruby_callback, ExceptionHierarchy, RubyError, Value};
+use mri_sys::VALUE;
+use std::fmt;
+
+exception_hierarchy! {
+    #[ruby(namespace = "Store", base = "Error")]
+    #[derive(Debug)]
+    enum StoreError {
+        NotFound { key: String, attempts: i64 },
+        ReadOnly,
+    }
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::NotFound { key, .. } => write!(fmt, "no such key: {}", key),
+            StoreError::ReadOnly => write!(fmt, "the store is read only"),
+        }
+    }
+}
+
+extern "C" fn missing_key(_receiver: VALUE) -> VALUE {
+    ruby_callback(|| -> Result<Value, StoreError> { Err(StoreError::NotFound { key: "a".to_owned(), attempts: 2 }) })
+}
+
+extern "C" fn read_only(_receiver: VALUE) -> VALUE {
+    ruby_callback(|| -> Result<Value, StoreError> { Err(StoreError::ReadOnly) })
+}
+
+extern "C" fn outside_namespace(_receiver: VALUE) -> VALUE {
+    ruby_callback(|| -> Result<Value, RubyError> { Err(RubyError::in_namespace(run("Store")?, "ArgumentError", "not ours")) })
+}
+
+extern "C" fn nul_attribute(_receiver: VALUE) -> VALUE {
+    ruby_callback(|| -> Result<Value, RubyError> {
+        Err(RubyError::in_namespace(run("Store")?, "NotFound", "bad").with_attribute("k\0ey", Value::NIL))
+    })
+}
+
+#[test]
+fn exception_hierarchies_carry_attributes() {
+    common::with_vm(|| {
+        let store = Value::from(unsafe { mri_sys::rb_define_module(b"Store\0".as_ptr() as _) });
+        let base = StoreError::define_exception_classes().unwrap();
+        assert_eq!(base.to_s(), Ok("Store::Error".to_owned()));
+        unsafe {
+            mri_sys::rb_define_global_function(b"missing_key\0".as_ptr() as _, missing_key as *mut _, 0);
+            mri_sys::rb_define_global_function(b"read_only\0".as_ptr() as _, read_only as *mut _, 0);
+            mri_sys::rb_define_global_function(b"outside_namespace\0".as_ptr() as _, outside_namespace as *mut _, 0);
+            mri_sys::rb_define_global_function(b"nul_attribute\0".as_ptr() as _, nul_attribute as *mut _, 0);
+        }
+
+        assert_eq!(ruby("begin; missing_key; rescue Store::Error => e; e.key; end").to_s(), Ok("a".to_owned()));
+        assert_eq!(ruby("begin; missing_key; rescue Store::NotFound => e; e.attempts; end").to::<i64>(), Ok(2));
+        assert_eq!(ruby("begin; missing_key; rescue => e; e.message; end").to_s(), Ok("no such key: a".to_owned()));
+        assert_eq!(ruby("begin; read_only; rescue Store::Error => e; e.class.name; end").to_s(), Ok("Store::ReadOnly".to_owned()));
+
+        // Only the namespace itself is searched, so top level classes are not picked up.
+        assert_eq!(run("outside_namespace").unwrap_err().exception_class_name, "NameError");
+
+        assert_eq!(run("nul_attribute").unwrap_err().exception_class_name, "ArgumentError");
+        let e = ExceptionHierarchy::new(store, "Bad\0Error").define().unwrap_err();
+        assert_eq!(e.exception_class_name, "ArgumentError");
+        let e = ExceptionHierarchy::new(store, "Failure").subclass("Missing", &["na\0me"]).define().unwrap_err();
+        assert_eq!(e.exception_class_name, "ArgumentError");
+    });
+}
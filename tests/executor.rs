@@ -0,0 +1,55 @@
+#![cfg(feature = "helpers")]
+
+use mri_sys::helpers::executor::{self, RunFuture, Stopped};
+use mri_sys::helpers::{eval, Binding};
+use std::future::Future;
+use std::panic;
+use std::pin::Pin;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+fn noop_waker() -> Waker {
+    fn clone(_: *const ()) -> RawWaker { RawWaker::new(std::ptr::null(), &VTABLE) }
+    fn noop(_: *const ()) { }
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+
+    unsafe { Waker::from_raw(clone(std::ptr::null())) }
+}
+
+/// Polls with a waker that does nothing until the future completes.
+fn block_on<R>(mut future: RunFuture<R>) -> Result<R, Stopped> {
+    let waker = noop_waker();
+    let mut context = Context::from_waker(&waker);
+
+    loop {
+        match Pin::new(&mut future).poll(&mut context) {
+            Poll::Ready(result) => return result,
+            Poll::Pending => std::thread::sleep(std::time::Duration::from_millis(1)),
+        }
+    }
+}
+
+fn eval_to_string(code: &'static str) -> String {
+    eval(code, Binding::top_level(), None).unwrap().to_s().unwrap()
+}
+
+#[test]
+fn executor_runs_closures_on_the_interpreter_thread() {
+    let handle = executor::spawn().unwrap();
+
+    assert_eq!(handle.run(|_| std::thread::current().name().map(str::to_owned)), Ok(Some("ruby".to_owned())));
+    assert_eq!(block_on(handle.clone().run_async(|_| eval_to_string("6 * 7"))), Ok("42".to_owned()));
+
+    let panic = panic::catch_unwind(|| handle.run(|_| panic!("on the executor"))).unwrap_err();
+    assert_eq!(panic.downcast_ref::<&str>(), Some(&"on the executor"));
+    let future = handle.run_async(|_| panic!("in a future"));
+    let panic = panic::catch_unwind(panic::AssertUnwindSafe(|| block_on(future))).unwrap_err();
+    assert_eq!(panic.downcast_ref::<&str>(), Some(&"in a future"));
+
+    // Work queued before the last handle goes away still runs.
+    let pending = handle.run_async(|_| eval_to_string("1 + 1"));
+    drop(handle);
+    assert_eq!(block_on(pending), Ok("2".to_owned()));
+
+    // The interpreter has been cleaned up and cannot be started again.
+    assert!(executor::spawn().is_err());
+}
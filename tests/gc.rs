@@ -0,0 +1,30 @@
+#![cfg(feature = "helpers")]
+
+use mri_sys::helpers::{eval, gc, Binding, Vm};
+use mri_sys::value_type::{T_NONE, T_STRING};
+
+#[test]
+fn gc_helpers_report_and_control_the_collector() {
+    let vm = Vm::init().unwrap();
+
+    let before = gc::stats().unwrap();
+    gc::start(true, true).unwrap();
+    gc::start(false, false).unwrap();
+    let after = gc::stats().unwrap();
+
+    assert!(after.count >= before.count + 2, "{:?} then {:?}", before, after);
+    assert!(after.major_gc_count > before.major_gc_count);
+    assert!(after.heap_live_slots > 0);
+
+    assert!(!gc::disable());
+    assert!(gc::disable());
+    assert_eq!(eval("GC.disable", Binding::top_level(), None).unwrap().to_s().unwrap(), "true");
+    assert!(gc::enable());
+    assert!(!gc::enable());
+
+    let counts = gc::count_objects().unwrap();
+    assert!(counts[&T_STRING] > 0);
+    assert!(counts.contains_key(&T_NONE));
+
+    assert_eq!(vm.shutdown(), 0);
+}
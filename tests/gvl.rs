@@ -0,0 +1,31 @@
+#![cfg(feature = "helpers")]
+
+use mri_sys::helpers::{eval, without_gvl, Binding, Cancel, Vm};
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[test]
+fn without_gvl_returns_resumes_panics_and_cancels() {
+    let vm = Vm::init().unwrap();
+
+    assert_eq!(without_gvl(|| 2 + 2, Cancel::Never).unwrap(), 4);
+
+    let panic = panic::catch_unwind(|| without_gvl(|| panic!("without the GVL"), Cancel::Never)).unwrap_err();
+    assert_eq!(panic.downcast_ref::<&str>(), Some(&"without the GVL"));
+
+    // Another Ruby thread interrupts the main thread while it waits without the GVL.
+    eval("Thread.new { sleep 0.1; Thread.main.raise 'interrupted' }", Binding::top_level(), None).unwrap();
+
+    let cancelled = AtomicBool::new(false);
+    let cancel = || cancelled.store(true, Ordering::SeqCst);
+    let result = without_gvl(|| {
+        while !cancelled.load(Ordering::SeqCst) {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+    }, Cancel::With(&cancel));
+
+    assert_eq!(result.unwrap_err().message, "interrupted");
+    assert!(cancelled.load(Ordering::SeqCst));
+
+    assert_eq!(vm.shutdown(), 0);
+}
@@ -0,0 +1,24 @@
+#![cfg(feature = "helpers")]
+
+use mri_sys::helpers::{eval, iseq, Binding, SourceLocation, Vm};
+
+#[test]
+fn precompiled_and_fallback_code_run_alike() {
+    let vm = Vm::init().unwrap();
+    eval("secret = 1", Binding::top_level(), None).unwrap();
+
+    let code = "[defined?(secret).inspect, __FILE__, 5 + 5].join(' ')";
+    let location = SourceLocation::new("precompiled.rb", 1);
+    let binary = iseq::compile_to_binary(code, location).unwrap();
+
+    let precompiled = iseq::eval_precompiled(&binary, code, location).unwrap().to_s().unwrap();
+    // Collects warnings instead of printing them.
+    eval("$stderr = Object.new; def $stderr.write(*s); ($warnings ||= []).concat(s).size; end", Binding::top_level(), None).unwrap();
+    let fallback = iseq::eval_precompiled(b"not an iseq", code, location).unwrap().to_s().unwrap();
+    let warnings = eval("$stderr = STDERR; $warnings.join", Binding::top_level(), None).unwrap().to_s().unwrap();
+    assert!(warnings.starts_with("precompiled.rb: precompiled code failed to load"), "{}", warnings);
+    assert_eq!(precompiled, "nil precompiled.rb 10");
+    assert_eq!(fallback, precompiled);
+
+    assert_eq!(vm.shutdown(), 0);
+}
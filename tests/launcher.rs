@@ -0,0 +1,29 @@
+#![cfg(feature = "helpers")]
+
+use mri_sys::build_info;
+use mri_sys::helpers::launcher;
+use std::ffi::OsString;
+use std::sync::Mutex;
+
+static ORDER: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+#[test]
+fn info_describes_the_build() {
+    let info = launcher::info();
+
+    assert!(info.starts_with(&format!("mri-sys {}\n", env!("CARGO_PKG_VERSION"))), "{}", info);
+    assert!(info.contains(&format!("link: {} {}\n", build_info::LINK_KIND, build_info::RUBY_LIB)), "{}", info);
+}
+
+#[test]
+fn init_hooks_run_in_registration_order() {
+    assert_eq!(launcher::main_with(&["mri-run".into(), launcher::INFO_FLAG.into()]), 0);
+
+    for i in 1..=3 {
+        launcher::register_init_hook(move |_| ORDER.lock().unwrap().push(i));
+    }
+
+    let arguments: Vec<OsString> = vec!["mri-run".into(), "-e".into(), "exit 7".into()];
+    assert_eq!(launcher::main_with(&arguments), 7);
+    assert_eq!(*ORDER.lock().unwrap(), vec![1, 2, 3]);
+}
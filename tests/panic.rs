@@ -0,0 +1,30 @@
+#![cfg(feature = "helpers")]
+
+use mri_sys::helpers::{catch_unwind, eval, ruby_callback, Binding, RubyError, Value, Vm};
+use mri_sys::VALUE;
+use std::panic;
+
+extern "C" fn panic_with(_receiver: VALUE, message: VALUE) -> VALUE {
+    ruby_callback(|| -> Result<Value, RubyError> { panic!("{}", Value::from(message).to::<String>().unwrap()) })
+}
+
+#[test]
+fn panics_cross_ruby_frames_and_resume_in_rust() {
+    let vm = Vm::init().unwrap();
+
+    let payload = panic::catch_unwind(|| catch_unwind(|| panic!("boom"))).unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"boom"));
+
+    let payload = panic::catch_unwind(|| catch_unwind(|| -> Value { panic!("{}", 42) })).unwrap_err();
+    assert_eq!(payload.downcast_ref::<String>().map(String::as_str), Some("42"));
+
+    assert_eq!(eval("RustPanic.ancestors.include?(StandardError)", Binding::top_level(), None), Ok(Value::TRUE));
+
+    // Each `RustPanic` carries its own payload, so a second panic doesn't replace the first.
+    unsafe { mri_sys::rb_define_global_function(b"panic_with\0".as_ptr() as _, panic_with as *mut _, 1) };
+    let code = "begin; panic_with 'first'; rescue RustPanic => e; begin; panic_with 'second'; rescue RustPanic; end; raise e; end";
+    let payload = panic::catch_unwind(|| eval(code, Binding::top_level(), None)).unwrap_err();
+    assert_eq!(payload.downcast_ref::<String>().map(String::as_str), Some("first"));
+
+    assert_eq!(vm.shutdown(), 0);
+}
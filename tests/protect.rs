@@ -0,0 +1,83 @@
+#![cfg(feature = "helpers")]
+
+mod common;
+
+use common::{ruby, run};
+use mri_sys::helpers::{catch_unwind, protect, record_throw_values, ToRuby, Unwind, Value};
+use mri_sys::VALUE;
+use std::sync::Mutex;
+
+/// How `through_rust` was last left, as `(state, tag or value, thrown value)`.
+static LAST_UNWIND: Mutex<Option<(i32, VALUE, Option<VALUE>)>> = Mutex::new(None);
+
+/// Yields to the block through `protect`, records how the block escaped and resumes the escape.
+extern "C" fn through_rust(_receiver: VALUE) -> VALUE {
+    let unwind = match protect(|| Value::from(unsafe { mri_sys::rb_yield(mri_sys::Qnil) })) {
+        Ok(value) => return value.0,
+        Err(unwind) => unwind,
+    };
+
+    let recorded = match unwind {
+        Unwind::Throw { tag, value } => (unwind.state(), tag.0, value.map(|value| value.0)),
+        Unwind::Break(value) => (unwind.state(), value.0, None),
+        _ => (unwind.state(), mri_sys::Qnil, None),
+    };
+    *LAST_UNWIND.lock().unwrap() = Some(recorded);
+
+    unsafe { unwind.resume() }
+}
+
+/// Yields to the block through `catch_unwind`, returning the message of whatever escaped.
+extern "C" fn swallow(_receiver: VALUE) -> VALUE {
+    let message = match catch_unwind(|| Value::from(unsafe { mri_sys::rb_yield(mri_sys::Qnil) })) {
+        Ok(..) => String::new(),
+        Err(e) => format!("{}: {}", e.exception_class_name, e.message),
+    };
+
+    message.to_ruby().0
+}
+
+fn last_unwind() -> (i32, VALUE, Option<VALUE>) {
+    LAST_UNWIND.lock().unwrap().take().expect("nothing escaped through Rust")
+}
+
+#[test]
+fn protect_reports_and_resumes_unwinds() {
+    common::with_vm(|| {
+        unsafe {
+            mri_sys::rb_define_global_function(b"through_rust\0".as_ptr() as _, through_rust as *mut _, 0);
+            mri_sys::rb_define_global_function(b"swallow\0".as_ptr() as _, swallow as *mut _, 0);
+        }
+
+        // The thrown value is only known once recording has been asked for.
+        assert_eq!(ruby("catch(:done) { through_rust { throw :done, 42 }; :not_thrown }"), ruby("42"));
+        assert_eq!(last_unwind(), (7, ruby(":done").0, None));
+
+        record_throw_values();
+        assert_eq!(ruby("catch(:done) { through_rust { through_rust { throw :done, 42 } }; :not_thrown }"), ruby("42"));
+        assert_eq!(last_unwind(), (7, ruby(":done").0, Some(ruby("42").0)));
+
+        // A throw caught by Ruby leaves nothing behind, and the record is hidden from Ruby.
+        assert_eq!(ruby("through_rust { catch(:inner) { throw :inner, 1 } }"), ruby("1"));
+        assert!(LAST_UNWIND.lock().unwrap().is_none());
+        assert_eq!(ruby("[Thread.current.instance_variables, Thread.current.keys]").to_s().unwrap(), "[[], []]");
+
+        assert_eq!(ruby("catch(:done) { swallow { throw :done } }").to_s().unwrap(), "LocalJumpError: uncaught throw :done");
+
+        assert_eq!(ruby("[1, 2].map { |x| through_rust { break x * 10 } }").to_s().unwrap(), "[10, 20]");
+        assert_eq!(last_unwind(), (2, ruby("20").0, None));
+
+        assert_eq!(ruby("through_rust { 5 }"), ruby("5"));
+        assert!(LAST_UNWIND.lock().unwrap().is_none());
+
+        let e = run("through_rust { raise ArgumentError, 'escaped' }").unwrap_err();
+        assert_eq!((e.exception_class_name.as_str(), e.message.as_str()), ("ArgumentError", "escaped"));
+        assert_eq!(last_unwind().0, 6);
+
+        // Without a `catch`, `throw` raises instead.
+        match protect(|| unsafe { Value::from(mri_sys::rb_eval_string(b"throw :done\0".as_ptr() as _)) }) {
+            Err(Unwind::Raise(e)) => assert_eq!(e.exception_class_name, "UncaughtThrowError"),
+            other => panic!("expected a raise, got {:?}", other),
+        }
+    });
+}
@@ -0,0 +1,34 @@
+#![cfg(feature = "helpers")]
+
+mod common;
+
+use common::{ruby, run};
+use mri_sys::helpers::{ruby_callback, RubyError, RubyErrorKind, Value};
+use mri_sys::VALUE;
+
+extern "C" fn fail_with_cause(_receiver: VALUE) -> VALUE {
+    ruby_callback(|| {
+        let cause = run("RuntimeError.new('root')")?;
+        Err(RubyError::new(Value::from(unsafe { mri_sys::rb_eArgError }), "bad").with_cause(cause))
+    })
+}
+
+extern "C" fn panic_in_callback(_receiver: VALUE) -> VALUE {
+    ruby_callback(|| -> Result<Value, RubyError> { panic!("in a callback") })
+}
+
+#[test]
+fn ruby_callbacks_raise_errors_after_returning() {
+    common::with_vm(|| {
+        unsafe {
+            mri_sys::rb_define_global_function(b"fail_with_cause\0".as_ptr() as _, fail_with_cause as *mut _, 0);
+            mri_sys::rb_define_global_function(b"panic_in_callback\0".as_ptr() as _, panic_in_callback as *mut _, 0);
+        }
+
+        let e = run("fail_with_cause").unwrap_err();
+        assert_eq!((e.kind(), e.message.as_str()), (RubyErrorKind::ArgumentError, "bad"));
+        assert_eq!(e.cause().map(|cause| cause.message.as_str()), Some("root"));
+
+        assert_eq!(ruby("begin; panic_in_callback; rescue RustPanic => e; e.message; end").to_s().unwrap(), "in a callback");
+    });
+}
@@ -0,0 +1,34 @@
+#![cfg(feature = "helpers")]
+
+use mri_sys::helpers::{run_main_with, std::classes, to_ruby};
+use std::ffi::OsString;
+use std::fs;
+
+#[test]
+fn run_main_behaves_like_ruby_executable() {
+    // Rejected before the interpreter starts, so it can still run below.
+    assert_eq!(run_main_with(&[], |_| panic!("started without arguments")), 1);
+
+    let directory = std::env::temp_dir().join(format!("mri-sys-run-main-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join("mri_sys_library.rb"), "LIBRARY = 'from -r'\n").unwrap();
+    let script = directory.join("script.rb");
+    fs::write(&script, "exit(PRELOADED == 'from rust' && LIBRARY == 'from -r' && ARGV == ['x'] ? 3 : 4)\n").unwrap();
+
+    let arguments: Vec<OsString> = vec![
+        "ruby".into(),
+        "-I".into(),
+        directory.clone().into(),
+        "-r".into(),
+        "mri_sys_library".into(),
+        script.into(),
+        "x".into(),
+    ];
+
+    let status = run_main_with(&arguments, |_| {
+        classes::Object().set_constant("PRELOADED", *to_ruby::string("from rust")).unwrap();
+    });
+
+    fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(status, 3);
+}
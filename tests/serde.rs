@@ -0,0 +1,80 @@
+#![cfg(feature = "serde")]
+
+mod common;
+
+use common::ruby;
+use mri_sys::helpers::serde::{from_value, to_value, Error};
+use mri_sys::helpers::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Order {
+    id: u64,
+    customer: Option<String>,
+    lines: Vec<Line>,
+    status: Status,
+    #[serde(with = "serde_bytes_as_vec")]
+    signature: Vec<u8>,
+    totals: BTreeMap<String, f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Line(String, u32);
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Status {
+    Open,
+    Shipped { tracking: String },
+    Refunded(f64),
+}
+
+/// Serializes bytes with `serialize_bytes`, like the `serde_bytes` crate.
+mod serde_bytes_as_vec {
+    pub fn serialize<S: serde::Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        struct Bytes;
+
+        impl<'de> serde::de::Visitor<'de> for Bytes {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result { fmt.write_str("bytes") }
+            fn visit_byte_buf<E>(self, bytes: Vec<u8>) -> Result<Vec<u8>, E> { Ok(bytes) }
+        }
+
+        deserializer.deserialize_byte_buf(Bytes)
+    }
+}
+
+#[test]
+fn serde_models_round_trip() {
+    common::with_vm(|| {
+        let order = Order {
+            id: u64::MAX,
+            customer: None,
+            lines: vec![Line("widget".to_owned(), 3)],
+            status: Status::Shipped { tracking: "1Z".to_owned() },
+            signature: vec![0xff, 0x00],
+            totals: vec![("net".to_owned(), 9.5)].into_iter().collect(),
+        };
+
+        let value = to_value(&order).unwrap();
+        ruby("def check_order(order)\n  order[:status][:Shipped][:tracking] == '1Z' && order[:signature].encoding == Encoding::BINARY && order[:id] == 2**64 - 1\nend");
+        assert_eq!(ruby("method(:check_order)").send("call", &[value]), Ok(Value::TRUE));
+        assert_eq!(from_value::<Order>(value).unwrap(), order);
+
+        assert_eq!(to_value(&Status::Open).unwrap().inspect().unwrap().to_s().unwrap(), ":Open");
+        assert_eq!(from_value::<Status>(ruby("{ Refunded: 2 }")).unwrap(), Status::Refunded(2.0));
+        assert!(from_value::<u8>(ruby("256")).is_err());
+        assert!(from_value::<Status>(ruby(":Lost")).is_err());
+
+        // Symbol names are copied out whole, and rejected if they aren't UTF-8.
+        assert_eq!(from_value::<String>(ruby(":\"a\\0b\"")).unwrap(), "a\0b");
+        let invalid = ruby("\"\\xff\".b.to_sym");
+        assert!(matches!(from_value::<String>(invalid), Err(Error::Ruby(ref e)) if e.exception_class_name == "EncodingError"));
+        assert!(matches!(from_value::<Status>(invalid), Err(Error::Ruby(ref e)) if e.exception_class_name == "EncodingError"));
+    });
+}
@@ -0,0 +1,20 @@
+#![cfg(feature = "helpers")]
+
+use mri_sys::helpers::{check_syntax, Vm};
+
+#[test]
+fn check_syntax_reports_diagnostics() {
+    let vm = Vm::init().unwrap();
+
+    assert_eq!(check_syntax("x = 1 + 1", "rules.rb"), Ok(()));
+
+    let diagnostics = check_syntax("x = 1\nx = (2 +\n", "rules.rb").unwrap_err();
+    assert!(!diagnostics.is_empty());
+    assert!(diagnostics.iter().all(|diagnostic| diagnostic.line >= 2), "{:?}", diagnostics);
+
+    let diagnostic = &check_syntax("x = 1\nfoo(1 2)\n", "rules.rb").unwrap_err()[0];
+    assert_eq!((diagnostic.line, diagnostic.column), (2, Some(6)), "{:?}", diagnostic);
+    assert!(diagnostic.message.contains("unexpected"), "{:?}", diagnostic);
+
+    assert_eq!(vm.shutdown(), 0);
+}
@@ -0,0 +1,14 @@
+#![cfg(feature = "helpers")]
+
+use mri_sys::helpers::{InitError, Vm};
+
+#[test]
+fn vm_can_only_be_initialised_once() {
+    let vm = Vm::init().unwrap();
+
+    assert_eq!(Vm::init().err(), Some(InitError::AlreadyInitialized));
+    assert_eq!(std::thread::spawn(|| Vm::init().err()).join().unwrap(), Some(InitError::AlreadyInitialized));
+
+    assert_eq!(vm.shutdown(), 0);
+    assert_eq!(Vm::init().err(), Some(InitError::CleanedUp));
+}
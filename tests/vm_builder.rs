@@ -0,0 +1,35 @@
+#![cfg(feature = "helpers")]
+
+use mri_sys::helpers::{eval, Binding, InitError, Verbosity, VmBuilder};
+
+#[test]
+fn builder_vm_can_require_stdlib() {
+    let invalid = VmBuilder::new().arguments(vec!["nul\0byte"]).init();
+    assert_eq!(invalid.err(), Some(InitError::InvalidArgument("nul\0byte".to_owned())));
+
+    let vm = VmBuilder::new()
+        .script_name("vm_builder_test.rb")
+        .arguments(vec!["first", "second"])
+        .verbosity(Verbosity::Silent)
+        .default_external_encoding("UTF-8")
+        .init()
+        .unwrap();
+
+    let run = |code: &str| eval(code, Binding::top_level(), None).unwrap();
+
+    for library in &["json", "set", "time", "stringio"] {
+        if let Err(e) = eval(&format!("require {:?}", library), Binding::top_level(), None) {
+            panic!("could not require {}: {}", library, e);
+        }
+    }
+
+    assert_eq!(run(r#"JSON.generate("a" => [1, 2])"#).to_s().unwrap(), r#"{"a":[1,2]}"#);
+    assert_eq!(run("Set[1, 1, 2].size").to_s().unwrap(), "2");
+    assert_eq!(run("$0").to_s().unwrap(), "vm_builder_test.rb");
+    assert_eq!(run("ARGV.join(',')").to_s().unwrap(), "first,second");
+    assert!(run("$VERBOSE").is_nil());
+    assert_eq!(run("Encoding.default_external.name").to_s().unwrap(), "UTF-8");
+    assert_eq!(run(r#""héllo".encode("UTF-16LE").bytesize"#).to_s().unwrap(), "10");
+
+    assert_eq!(vm.shutdown(), 0);
+}